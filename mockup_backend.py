@@ -1,11 +1,47 @@
-from flask import Flask, jsonify, request
+from flask import Flask, jsonify, request, send_file, url_for
 import random
 from flask_cors import CORS
 import time
+import uuid
+import io
+from PIL import Image
 
 app = Flask(__name__)
 CORS(app)
 
+# In-memory archive: uuid -> {filename, mimetype, data, tags, note, count, session, analysis, exif, sha256, hash}
+archive = {}
+
+def average_hash(data):
+    '''64-bit average hash: 8x8 grayscale thumbnail, one bit per pixel brighter than the mean.'''
+    try:
+        img = Image.open(io.BytesIO(data)).convert('L').resize((8, 8))
+    except Exception:
+        return None
+    pixels = list(img.getdata())
+    mean = sum(pixels) / len(pixels)
+    bits = 0
+    for p in pixels:
+        bits = (bits << 1) | (1 if p > mean else 0)
+    return bits
+
+def describe(key):
+    entry = archive[key]
+    return {
+        'uuid': key,
+        'filename': entry['filename'],
+        'tags': entry['tags'],
+        'note': entry['note'],
+        'count': entry['count'],
+        'session': entry['session'],
+        'analysis': entry['analysis'],
+        'exif': entry['exif'],
+        'sha256': entry['sha256'],
+        'download': url_for('download', key=key, _external=True),
+        'update': url_for('update', key=key, _external=True),
+        'delete': url_for('delete', key=key, _external=True),
+    }
+
 @app.route('/analyze', methods=['POST'])
 def analyze():
     time.sleep(5)
@@ -15,6 +51,80 @@ def analyze():
             data[file.filename] = {'overall_class': {'шипун':random.random(), 'кликун': random.random(), 'малый': random.random()}}
     return jsonify(data)
 
+@app.route('/save', methods=['POST'])
+def save():
+    saved = []
+    tags = request.form.get('tags', '').split()
+    for file in request.files.getlist('f[]'):
+        if file:
+            key = str(uuid.uuid4())
+            data = file.read()
+            archive[key] = {
+                'filename': file.filename,
+                'mimetype': file.mimetype,
+                'data': data,
+                'tags': tags,
+                'note': request.form.get('note', ''),
+                'count': request.form.get('count', 1, type=int),
+                'session': request.form.get('session'),
+                'analysis': request.form.get('analysis'),
+                'exif': request.form.get('exif'),
+                'sha256': request.form.get('sha256'),
+                'hash': average_hash(data),
+            }
+            saved.append(describe(key))
+    return jsonify(saved)
+
+@app.route('/image')
+def search():
+    sha256 = request.args.get('sha256')
+    if sha256:
+        return jsonify([describe(key) for key, entry in archive.items() if entry['sha256'] == sha256])
+    wanted = request.args.get('tags', '').replace('+', ' ').split()
+    return jsonify([describe(key) for key, entry in archive.items() if all(t in entry['tags'] for t in wanted)])
+
+@app.route('/image/<key>')
+def download(key):
+    entry = archive[key]
+    return send_file(io.BytesIO(entry['data']), mimetype=entry['mimetype'], download_name=entry['filename'])
+
+@app.route('/image/<key>/update', methods=['POST'])
+def update(key):
+    archive[key]['tags'] = request.form.get('tags', '').split()
+    return jsonify(describe(key))
+
+@app.route('/image/<key>/delete', methods=['POST'])
+def delete(key):
+    archive.pop(key, None)
+    return jsonify({})
+
+@app.route('/tags')
+def tags():
+    counts = {}
+    for entry in archive.values():
+        for tag in entry['tags']:
+            counts[tag] = counts.get(tag, 0) + 1
+    return jsonify(counts)
+
+@app.route('/similar', methods=['POST'])
+def similar():
+    files = request.files.getlist('f[]')
+    if not files:
+        return jsonify({'error': 'no image'}), 400
+    query = average_hash(files[0].read())
+    if query is None:
+        return jsonify({'error': 'not an image'}), 400
+    results = []
+    for key, entry in archive.items():
+        if entry['hash'] is None:
+            continue
+        distance = bin(query ^ entry['hash']).count('1')
+        result = describe(key)
+        result['score'] = 1 - distance / 64
+        results.append(result)
+    results.sort(key=lambda r: r['score'], reverse=True)
+    return jsonify(results)
+
 @app.route('/')
 def index():
     return '''
@@ -0,0 +1,236 @@
+//! Requests to the archive backend that are shared between views.
+
+use std::collections::HashMap;
+
+use reqwest::multipart::Part;
+
+use crate::components::file_upload_box::FileDetails;
+use crate::components::tag_cloud::TagCounts;
+use crate::root_url;
+use crate::views::image_analysis::{ImageAnalysisData, ImageAnalysisOutcome};
+
+//const ANALYSIS_URL: &str = "http://localhost:5000/analyze";
+const ANALYSIS_URL: &str = concat!(root_url!(), "/analyze");
+const SEARCH_URL: &str = concat!(root_url!(), "/image");
+const TAGS_URL: &str = concat!(root_url!(), "/tags");
+const SIMILAR_URL: &str = concat!(root_url!(), "/similar");
+
+#[derive(serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    pub filename: String,
+    pub delete: String,
+    pub download: String,
+    pub uuid: String,
+    pub update: String,
+    pub tags: Vec<String>,
+    /// Hex-encoded SHA-256 of the original file, sent along with it when it was uploaded.
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// Free-text note given when the image was uploaded.
+    #[serde(default)]
+    pub note: Option<String>,
+    /// The analysis outcome stored when the image was uploaded.
+    #[serde(default)]
+    pub analysis: Option<serde_json::Value>,
+    /// How similar the image is to the example, from 0 to 1; only set for query-by-example.
+    #[serde(default)]
+    pub score: Option<f64>,
+}
+
+impl SearchResult {
+    /// The classification stored alongside the image, if it was analyzed before archiving.
+    pub fn stored_analysis(&self) -> Option<ImageAnalysisData> {
+        // The archive keeps the `analysis` form field as-is, so it may come back as a JSON string.
+        let value = match self.analysis.as_ref()? {
+            serde_json::Value::String(s) => serde_json::from_str(s).ok()?,
+            v => v.clone(),
+        };
+        match serde_json::from_value(value).ok()? {
+            ImageAnalysisOutcome::Analyzed(data) => Some(data),
+            _ => None,
+        }
+    }
+}
+
+/// Classification results keyed by file name.
+pub type AnalysisResponse = HashMap<String, ImageAnalysisData>;
+
+async fn get_json<T: serde::de::DeserializeOwned>(url: &str, what: &str) -> Result<T, String> {
+    let client = reqwest::Client::new();
+    let request = client.get(url).send().await;
+
+    match request {
+        Ok(resp) => match resp.error_for_status() {
+            Ok(resp) => match resp.json().await {
+                Ok(d) => Ok(d),
+                Err(why) => Err(format!("Error in {what} JSON: {why}")),
+            },
+            Err(why) => Err(format!("Error status in {what}: {why}")),
+        },
+        Err(why) => Err(format!("Error sending {what} request: {why}")),
+    }
+}
+
+/// A multipart file part for the image, read from its blob for just this request.
+pub async fn file_part(file: &FileDetails) -> Result<Part, String> {
+    Ok(Part::bytes(file.read().await?)
+        .file_name(file.name.clone())
+        .mime_str(&file.file_type)
+        .unwrap())
+}
+
+/// Send images to the classifier, one file part (see [`file_part`]) per image.
+pub async fn analyze(files: Vec<Part>) -> Result<AnalysisResponse, String> {
+    let client = reqwest::Client::new();
+    let mut body = reqwest::multipart::Form::new();
+    for file in files {
+        body = body.part("f[]", file);
+    }
+
+    let request = client.post(ANALYSIS_URL).multipart(body).send().await;
+    if let Ok(resp) = request {
+        let data: Result<AnalysisResponse, _> = resp.json().await;
+        if let Ok(data) = data {
+            Ok(data)
+        } else {
+            Err(format!(
+                "Data returned was not valid JSON: {}",
+                data.unwrap_err()
+            ))
+        }
+    } else {
+        Err(format!(
+            "Error while sending request: {}",
+            request.unwrap_err()
+        ))
+    }
+}
+
+/// Find archived images having all of the space-separated tags.
+pub async fn search(tags: &str) -> Result<Vec<SearchResult>, String> {
+    let query = urlencoding::encode(&tags.replace(' ', "+")).into_owned();
+    get_json(&format!("{SEARCH_URL}?tags={query}"), "search").await
+}
+
+/// Find archived copies of a file by the hex SHA-256 of its contents.
+pub async fn find_by_hash(sha256: &str) -> Result<Vec<SearchResult>, String> {
+    let query = urlencoding::encode(sha256).into_owned();
+    let found: Vec<SearchResult> =
+        get_json(&format!("{SEARCH_URL}?sha256={query}"), "archive lookup").await?;
+    // An archive that does not know the parameter returns unrelated images.
+    Ok(found
+        .into_iter()
+        .filter(|entry| entry.sha256.as_deref() == Some(sha256))
+        .collect())
+}
+
+/// Find archived images that look like the given one, most similar first.
+pub async fn similar(file: &FileDetails) -> Result<Vec<SearchResult>, String> {
+    let client = reqwest::Client::new();
+    let body = reqwest::multipart::Form::new().part("f[]", file_part(file).await?);
+    let request = client.post(SIMILAR_URL).multipart(body).send().await;
+
+    match request {
+        Ok(resp) => match resp.error_for_status() {
+            Ok(resp) => match resp.json().await {
+                Ok(d) => Ok(d),
+                Err(why) => Err(format!("Error in similarity search JSON: {why}")),
+            },
+            Err(why) => Err(format!("Error status in similarity search: {why}")),
+        },
+        Err(why) => Err(format!("Error sending similarity search request: {why}")),
+    }
+}
+
+/// Every tag in the archive, with the number of images having it.
+pub async fn tags() -> Result<TagCounts, String> {
+    get_json(TAGS_URL, "tag list").await
+}
+
+/// Replace the tags of an archived image.
+pub async fn update_tags(item: &SearchResult, tags: &[String]) -> Result<(), String> {
+    let client = reqwest::Client::new();
+    let body = reqwest::multipart::Form::new().part("tags", Part::text(tags.join(" ")));
+    let request = client.post(&item.update).multipart(body).send().await;
+
+    match request {
+        Ok(resp) => match resp.error_for_status() {
+            Ok(_resp) => Ok(()),
+            Err(why) => Err(format!(
+                "Error status in retagging {}: {why}",
+                item.filename
+            )),
+        },
+        Err(why) => Err(format!(
+            "Error sending retag request for {}: {why}",
+            item.filename
+        )),
+    }
+}
+
+/// Remove an image from the archive.
+pub async fn delete(item: &SearchResult) -> Result<(), String> {
+    let client = reqwest::Client::new();
+    let request = client.post(&item.delete).send().await;
+
+    match request {
+        Ok(resp) => match resp.error_for_status() {
+            Ok(_resp) => Ok(()),
+            Err(why) => Err(format!("Error status in deleting {}: {why}", item.filename)),
+        },
+        Err(why) => Err(format!(
+            "Error sending delete request for {}: {why}",
+            item.filename
+        )),
+    }
+}
+
+/// Fetch the original file of an archived image.
+pub async fn download(item: &SearchResult) -> Result<FileDetails, String> {
+    let request = reqwest::get(&item.download).await;
+
+    let resp = match request {
+        Ok(resp) => match resp.error_for_status() {
+            Ok(resp) => resp,
+            Err(why) => {
+                return Err(format!(
+                    "Error status in downloading {}: {why}",
+                    item.filename
+                ))
+            }
+        },
+        Err(why) => {
+            return Err(format!(
+                "Error sending download request for {}: {why}",
+                item.filename
+            ))
+        }
+    };
+    let file_type = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+    match resp.bytes().await {
+        Ok(data) => Ok(FileDetails::from_bytes(
+            item.filename.clone(),
+            file_type,
+            &data,
+        )),
+        Err(why) => Err(format!(
+            "Error reading download of {}: {why}",
+            item.filename
+        )),
+    }
+}
+
+/// Download an archived image and run it through the classifier again, on its own so
+/// that equal file names can't get mixed up.
+pub async fn reanalyze(item: &SearchResult) -> Result<ImageAnalysisData, String> {
+    let file = download(item).await?;
+    let mut response = analyze(vec![file_part(&file).await?]).await?;
+    response
+        .remove(&file.name)
+        .ok_or_else(|| "Server seems to have ignored the provided image?!".to_string())
+}
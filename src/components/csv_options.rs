@@ -0,0 +1,96 @@
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use crate::export::csv::{CsvColumn, CsvOptions};
+
+#[derive(Properties, PartialEq)]
+pub struct CsvOptionsProps {
+    pub options: CsvOptions,
+    pub on_change: Callback<CsvOptions>,
+}
+
+const DELIMITERS: [(char, &str); 3] = [(';', "Semicolon"), (',', "Comma"), ('\t', "Tab")];
+const DECIMAL_SEPARATORS: [(char, &str); 2] = [('.', "Point (0.5)"), (',', "Comma (0,5)")];
+
+fn separator_select(
+    choices: &'static [(char, &'static str)],
+    current: char,
+    on_select: Callback<char>,
+) -> Html {
+    let onchange = on_select.reform(move |ev: Event| {
+        let select: HtmlSelectElement = ev.target_unchecked_into();
+        select.value().chars().next().unwrap_or(choices[0].0)
+    });
+    html! {
+        <select class="form-select" {onchange}>
+            { for choices.iter().map(|(c, label)| html!(
+                <option value={c.to_string()} selected={*c == current}>{*label}</option>
+            )) }
+        </select>
+    }
+}
+
+#[function_component]
+pub fn CsvOptionsForm(props: &CsvOptionsProps) -> Html {
+    let columns = CsvColumn::ALL.iter().map(|column| {
+        let column = *column;
+        let checked = props.options.columns.contains(&column);
+        let onchange = {
+            let options = props.options.clone();
+            props.on_change.reform(move |ev: Event| {
+                let input: HtmlInputElement = ev.target_unchecked_into();
+                let mut options = options.clone();
+                // Rebuild from `ALL` so the column order stays fixed.
+                options.columns = CsvColumn::ALL
+                    .into_iter()
+                    .filter(|c| {
+                        if *c == column {
+                            input.checked()
+                        } else {
+                            options.columns.contains(c)
+                        }
+                    })
+                    .collect();
+                options
+            })
+        };
+        let id = format!("csv-column-{column:?}");
+        html! {
+            <div class="form-check form-check-inline">
+                <input class="form-check-input" type="checkbox" id={id.clone()} {checked} {onchange} />
+                <label class="form-check-label" for={id}>{column.label()}</label>
+            </div>
+        }
+    });
+
+    let on_delimiter = {
+        let options = props.options.clone();
+        props.on_change.reform(move |delimiter| CsvOptions {
+            delimiter,
+            ..options.clone()
+        })
+    };
+    let on_decimal = {
+        let options = props.options.clone();
+        props.on_change.reform(move |decimal_separator| CsvOptions {
+            decimal_separator,
+            ..options.clone()
+        })
+    };
+
+    html! {
+        <div>
+            <div class="mb-2">{ for columns }</div>
+            <div class="row g-2">
+                <div class="col-auto">
+                    <label class="form-label">{"Delimiter"}</label>
+                    {separator_select(&DELIMITERS, props.options.delimiter, on_delimiter)}
+                </div>
+                <div class="col-auto">
+                    <label class="form-label">{"Decimal separator"}</label>
+                    {separator_select(&DECIMAL_SEPARATORS, props.options.decimal_separator, on_decimal)}
+                </div>
+            </div>
+        </div>
+    }
+}
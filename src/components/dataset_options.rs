@@ -0,0 +1,80 @@
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use crate::components::form::on_input;
+use crate::export::dataset::{DatasetFormat, DatasetOptions, Split};
+
+#[derive(Properties, PartialEq)]
+pub struct DatasetOptionsProps {
+    pub options: DatasetOptions,
+    pub on_change: Callback<DatasetOptions>,
+}
+
+#[function_component]
+pub fn DatasetOptionsForm(props: &DatasetOptionsProps) -> Html {
+    let ratios = Split::ALL.iter().map(|split| {
+        let i = *split as usize;
+        let onchange = on_input(&props.options, &props.on_change, move |o, v: u32| o.ratio[i] = v);
+        html! {
+            <div class="col-2">
+                <label class="form-label">{format!("{} share", split.folder())}</label>
+                <input class="form-control" type="number" min="0" value={props.options.ratio[i].to_string()} {onchange} />
+            </div>
+        }
+    });
+
+    let on_reviewed_only = {
+        let options = props.options.clone();
+        props.on_change.reform(move |ev: Event| {
+            let input: HtmlInputElement = ev.target_unchecked_into();
+            DatasetOptions {
+                reviewed_only: input.checked(),
+                ..options.clone()
+            }
+        })
+    };
+    let on_format = {
+        let options = props.options.clone();
+        props.on_change.reform(move |ev: Event| {
+            let select: HtmlSelectElement = ev.target_unchecked_into();
+            let format = match select.value().as_str() {
+                "csv" => DatasetFormat::CsvManifest,
+                _ => DatasetFormat::ImageFolderZip,
+            };
+            DatasetOptions {
+                format,
+                ..options.clone()
+            }
+        })
+    };
+    let is_zip = props.options.format == DatasetFormat::ImageFolderZip;
+
+    html! {
+        <div class="row g-2 align-items-end">
+            { for ratios }
+            <div class="col-2">
+                <label class="form-label">{"Random seed"}</label>
+                <input class="form-control" type="number" min="0" value={props.options.seed.to_string()}
+                    onchange={on_input(&props.options, &props.on_change, |o, v: u64| o.seed = v)} />
+            </div>
+            <div class="col-2">
+                <label class="form-label">{"Minimum confidence"}</label>
+                <input class="form-control" type="number" min="0" max="1" step="0.05" value={props.options.min_confidence.to_string()}
+                    disabled={props.options.reviewed_only}
+                    onchange={on_input(&props.options, &props.on_change, |o, v: f64| o.min_confidence = v)} />
+            </div>
+            <div class="col-2">
+                <select class="form-select" onchange={on_format}>
+                    <option value="zip" selected={is_zip}>{"ImageFolder ZIP"}</option>
+                    <option value="csv" selected={!is_zip}>{"CSV manifest only"}</option>
+                </select>
+            </div>
+            <div class="col-12">
+                <div class="form-check">
+                    <input class="form-check-input" type="checkbox" id="dataset-reviewed-only" checked={props.options.reviewed_only} onchange={on_reviewed_only} />
+                    <label class="form-check-label" for="dataset-reviewed-only">{"Only use human-reviewed labels"}</label>
+                </div>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,69 @@
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use crate::components::form::on_input;
+use crate::imaging::{DownscaleOptions, OutputFormat};
+
+#[derive(Properties, PartialEq)]
+pub struct DownscaleOptionsProps {
+    pub options: DownscaleOptions,
+    pub on_change: Callback<DownscaleOptions>,
+}
+
+#[function_component]
+pub fn DownscaleOptionsForm(props: &DownscaleOptionsProps) -> Html {
+    let on_enabled = {
+        let options = props.options.clone();
+        props.on_change.reform(move |ev: Event| {
+            let input: HtmlInputElement = ev.target_unchecked_into();
+            DownscaleOptions {
+                enabled: input.checked(),
+                ..options.clone()
+            }
+        })
+    };
+    let on_format = {
+        let options = props.options.clone();
+        props.on_change.reform(move |ev: Event| {
+            let select: HtmlSelectElement = ev.target_unchecked_into();
+            let format = OutputFormat::ALL
+                .into_iter()
+                .find(|f| f.mime_type() == select.value())
+                .unwrap_or(options.format);
+            DownscaleOptions {
+                format,
+                ..options.clone()
+            }
+        })
+    };
+    let disabled = !props.options.enabled;
+
+    html! {
+        <div class="row g-2 align-items-end">
+            <div class="col-12">
+                <div class="form-check">
+                    <input class="form-check-input" type="checkbox" id="downscale-enabled" checked={props.options.enabled} onchange={on_enabled} />
+                    <label class="form-check-label" for="downscale-enabled">{"Downscale images before analysis (the archive still gets the originals)"}</label>
+                </div>
+            </div>
+            <div class="col-2">
+                <label class="form-label">{"Longest edge (px)"}</label>
+                <input class="form-control" type="number" min="32" step="32" value={props.options.max_edge.to_string()} {disabled}
+                    onchange={on_input(&props.options, &props.on_change, |o, v: u32| o.max_edge = v.max(1))} />
+            </div>
+            <div class="col-2">
+                <label class="form-label">{"Format"}</label>
+                <select class="form-select" onchange={on_format} {disabled}>
+                    { for OutputFormat::ALL.into_iter().map(|f| html!(
+                        <option value={f.mime_type()} selected={f == props.options.format}>{f.label()}</option>
+                    )) }
+                </select>
+            </div>
+            <div class="col-2">
+                <label class="form-label">{"Quality"}</label>
+                <input class="form-control" type="number" min="0.1" max="1" step="0.05" value={props.options.quality.to_string()} {disabled}
+                    onchange={on_input(&props.options, &props.on_change, |o, v: f64| o.quality = v.clamp(0.1, 1.0))} />
+            </div>
+        </div>
+    }
+}
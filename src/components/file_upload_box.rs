@@ -1,26 +1,83 @@
 use gloo::file::callbacks::FileReader;
-use gloo::file::File;
+use gloo::file::{Blob, File};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use web_sys::{DragEvent, Event, FileList, HtmlInputElement};
 use yew::html::TargetCast;
 use yew::prelude::*;
 use yew::{html, Callback, Component, Context, Html};
 
-#[derive(Clone, PartialEq)]
+use crate::imaging::BlobUrl;
+use crate::metadata::{read_exif, ExifData};
+use crate::privacy::EmbeddedMetadata;
+
+#[derive(Clone)]
 pub struct FileDetails {
     pub name: String,
     pub file_type: String,
-    pub data: Vec<u8>,
+    /// The file contents, kept on the browser side only; see [`FileDetails::read`].
+    pub blob: Blob,
+    /// Hex-encoded SHA-256 of the contents.
+    pub sha256: String,
+    /// CRC-32 of the contents, so that the file can go into a ZIP straight from `blob`.
+    pub crc32: u32,
+    pub exif: ExifData,
+    /// Metadata other than EXIF that the file carries.
+    pub embedded: EmbeddedMetadata,
+    /// Object URL of `blob`, revoked once the last copy of these details is dropped.
+    pub url: BlobUrl,
+}
+
+// `sha256` stands in for the contents, so that comparing props does not compare whole images.
+impl PartialEq for FileDetails {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.file_type == other.file_type && self.sha256 == other.sha256
+    }
+}
+
+impl FileDetails {
+    /// Details of `blob`, whose contents are `data`. Only `blob` is kept, so that
+    /// the contents are not held in WASM memory as well.
+    pub fn new(name: String, file_type: String, blob: Blob, data: &[u8]) -> Self {
+        let url = BlobUrl::new(blob.clone());
+        Self {
+            name,
+            file_type,
+            blob,
+            sha256: format!("{:x}", Sha256::digest(data)),
+            crc32: crc32fast::hash(data),
+            exif: read_exif(data),
+            embedded: EmbeddedMetadata::scan(data),
+            url,
+        }
+    }
+
+    /// Details of a file that is only in memory, e.g. one just downloaded.
+    pub fn from_bytes(name: String, file_type: String, data: &[u8]) -> Self {
+        let blob = Blob::new_with_options(data, Some(&file_type));
+        Self::new(name, file_type, blob, data)
+    }
+
+    /// Size of the file in bytes.
+    pub fn size(&self) -> u64 {
+        self.blob.size()
+    }
+
+    /// Read the contents back from the blob.
+    pub async fn read(&self) -> Result<Vec<u8>, String> {
+        gloo::file::futures::read_as_bytes(&self.blob)
+            .await
+            .map_err(|e| format!("Failed to read {}: {e}", self.name))
+    }
 }
 
 pub enum Msg {
-    Loaded(String, String, Vec<u8>),
+    Loaded(File, Vec<u8>),
     Files(Vec<File>),
 }
 
 pub struct FileUploadBox {
     readers: HashMap<String, FileReader>,
-    files: Vec<FileDetails>,
 }
 
 #[derive(Properties, PartialEq)]
@@ -36,36 +93,31 @@ impl Component for FileUploadBox {
     fn create(_ctx: &Context<Self>) -> Self {
         Self {
             readers: HashMap::default(),
-            files: vec![],
         }
     }
 
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
-            Msg::Loaded(file_name, file_type, data) => {
-                let file_details = FileDetails {
-                    data,
-                    file_type,
-                    name: file_name.clone(),
-                };
+            Msg::Loaded(file, data) => {
+                let file_name = file.name();
+                let file_type = file.raw_mime_type();
+                let blob = Blob::clone(&file);
+                let file_details = FileDetails::new(file_name.clone(), file_type, blob, &data);
                 self.readers.remove(&file_name);
-                ctx.props().on_image.emit(file_details.clone());
-                self.files.push(file_details);
-                true
+                ctx.props().on_image.emit(file_details);
+                false
             }
             Msg::Files(files) => {
                 for file in files.into_iter() {
                     let file_name = file.name();
-                    let file_type = file.raw_mime_type();
 
                     let task = {
                         let link = ctx.link().clone();
-                        let file_name = file_name.clone();
+                        let loaded = file.clone();
 
                         gloo::file::callbacks::read_as_bytes(&file, move |res| {
                             link.send_message(Msg::Loaded(
-                                file_name,
-                                file_type,
+                                loaded,
                                 res.expect("failed to read file"),
                             ))
                         })
@@ -78,13 +130,6 @@ impl Component for FileUploadBox {
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
-        let preview = if !self.files.is_empty() {
-            let rows = (&self.files).iter().map(|_f| html!());
-            html!({for rows})
-        } else {
-            html!()
-        };
-
         html! {
         <div class="card mb-3 text-bg-secondary"
             id="drop-container"
@@ -99,8 +144,6 @@ impl Component for FileUploadBox {
             ondragenter={Callback::from(|event: DragEvent| {
                 event.prevent_default();
             })}>
-            { preview }
-
             <div class="card-body">
                 <p>{"Drop or select a file to analyze it"}</p>
                 <input
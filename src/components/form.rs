@@ -0,0 +1,27 @@
+use std::str::FromStr;
+
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+/// A change callback for an input of a form that edits `value`: parses the input's
+/// value, hands it to `set` with a copy of `value` and reports the copy.
+/// Values that don't parse leave the copy unchanged.
+pub fn on_input<V, T>(
+    value: &V,
+    on_change: &Callback<V>,
+    set: impl Fn(&mut V, T) + 'static,
+) -> Callback<Event>
+where
+    V: Clone + 'static,
+    T: FromStr + 'static,
+{
+    let value = value.clone();
+    on_change.reform(move |ev: Event| {
+        let input: HtmlInputElement = ev.target_unchecked_into();
+        let mut value = value.clone();
+        if let Ok(parsed) = input.value().trim().parse() {
+            set(&mut value, parsed);
+        }
+        value
+    })
+}
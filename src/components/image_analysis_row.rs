@@ -1,15 +1,28 @@
+use web_sys::{HtmlInputElement, HtmlSelectElement, HtmlTextAreaElement};
 use yew::prelude::*;
 
 use crate::{
-    components::image_display::{get_image_url, ImageDisplayBox},
-    views::image_analysis::{ImageAnalysisOutcome, ImageAnalysisStatus},
+    components::{image_display::ImageDisplayBox, lazy_mount::use_lazy_mount},
+    imaging::Transform,
+    views::image_analysis::{ArchiveStatus, ImageAnalysisOutcome, ImageAnalysisStatus, ImageId},
 };
 
 #[derive(Properties, PartialEq)]
 pub struct AnalysisReportProps {
     pub image: ImageAnalysisStatus,
-    pub on_delete: Callback<MouseEvent>,
-    pub on_upload: Callback<MouseEvent>,
+    pub on_delete: Callback<ImageId>,
+    /// Called with whether to upload even if the archive has the image already.
+    pub on_upload: Callback<(ImageId, bool)>,
+    pub on_review: Callback<(ImageId, Option<String>)>,
+    pub on_transform: Callback<(ImageId, Transform)>,
+    /// Keep this frame of its burst group and collapse the others.
+    pub on_keep_frame: Callback<ImageId>,
+    pub on_expand: Callback<ImageId>,
+    /// Called with the tags typed into the row, split on whitespace.
+    pub on_tags: Callback<(ImageId, Vec<String>)>,
+    pub on_note: Callback<(ImageId, String)>,
+    /// Called with the number of individuals counted in the photo.
+    pub on_count: Callback<(ImageId, u32)>,
 }
 
 fn placeholder() -> Html {
@@ -28,6 +41,86 @@ fn placeholder() -> Html {
 
 #[function_component]
 pub fn AnalysisReportRow(props: &AnalysisReportProps) -> Html {
+    fn get_review_select(
+        img: &ImageAnalysisStatus,
+        on_review: &Callback<(ImageId, Option<String>)>,
+    ) -> Html {
+        let ImageAnalysisOutcome::Analyzed(res) = &img.outcome else {
+            return html!();
+        };
+        let mut classes: Vec<&String> = res.overall_classification.keys().collect();
+        classes.sort();
+        let id = img.id;
+        let onchange = on_review.reform(move |ev: Event| {
+            let select: HtmlSelectElement = ev.target_unchecked_into();
+            (id, Some(select.value()).filter(|v| !v.is_empty()))
+        });
+        html! {
+            <div class="input-group mb-3">
+                <label class="input-group-text">{"Reviewed label"}</label>
+                <select class="form-select" {onchange}>
+                    <option value="" selected={img.reviewed_label.is_none()}>{"Not reviewed"}</option>
+                    { for classes.into_iter().map(|c| html!(
+                        <option value={c.clone()} selected={img.reviewed_label.as_ref() == Some(c)}>{c}</option>
+                    )) }
+                </select>
+            </div>
+        }
+    }
+
+    fn get_tag_editor(
+        img: &ImageAnalysisStatus,
+        on_tags: &Callback<(ImageId, Vec<String>)>,
+        on_note: &Callback<(ImageId, String)>,
+        on_count: &Callback<(ImageId, u32)>,
+    ) -> Html {
+        let id = img.id;
+        let count = img.count;
+        let on_count = on_count.reform(move |ev: Event| {
+            let input: HtmlInputElement = ev.target_unchecked_into();
+            (id, input.value().parse().unwrap_or(count))
+        });
+        let on_tags = on_tags.reform(move |ev: Event| {
+            let input: HtmlInputElement = ev.target_unchecked_into();
+            (
+                id,
+                input.value().split_whitespace().map(String::from).collect(),
+            )
+        });
+        let on_note = on_note.reform(move |ev: Event| {
+            let textarea: HtmlTextAreaElement = ev.target_unchecked_into();
+            (id, textarea.value())
+        });
+        html! {
+            <fieldset class="mb-3" disabled={img.archive.is_sent()}>
+                <div class="input-group mb-2">
+                    <label class="input-group-text">{"Tags"}</label>
+                    <input type="text" class="form-control" placeholder="location, age class, behaviour ..." value={img.tags.join(" ")} onchange={on_tags} />
+                    <label class="input-group-text">{"Individuals"}</label>
+                    <input type="number" class="form-control flex-grow-0 w-auto" min="0" step="1" value={img.count.to_string()} onchange={on_count} />
+                </div>
+                <textarea class="form-control" rows="2" placeholder="Note" value={img.note.clone()} onchange={on_note} />
+            </fieldset>
+        }
+    }
+
+    fn get_exif_panel(img: &ImageAnalysisStatus) -> Html {
+        let fields = img.data.exif.summary();
+        if fields.is_empty() {
+            return html!(<p class="text-muted small">{"No photo metadata"}</p>);
+        }
+        html! {
+            <details class="mb-3">
+                <summary>{"Photo metadata"}</summary>
+                <table class="table table-sm mb-0">
+                    { for fields.into_iter().map(|(label, value)| html!(
+                        <tr><th>{label}</th><td>{value}</td></tr>
+                    )) }
+                </table>
+            </details>
+        }
+    }
+
     fn get_analysis_result(img: &ImageAnalysisStatus) -> Html {
         match &img.outcome {
             ImageAnalysisOutcome::WaitingToSend => html! {
@@ -76,15 +169,134 @@ pub fn AnalysisReportRow(props: &AnalysisReportProps) -> Html {
         }
     }
 
+    // The wrapper stays in place whether or not the row is mounted, so it can be observed.
+    let node = use_node_ref();
+    if let Some(height) = use_lazy_mount(node.clone()) {
+        return html!(<div ref={node} style={format!("height: {height}px")}></div>);
+    }
+
+    let id = props.image.id;
+    let on_upload = props.on_upload.reform(move |_: MouseEvent| (id, false));
+    let on_delete = props.on_delete.reform(move |_: MouseEvent| id);
+    let transform = props.image.transform;
+    let on_rotate = props
+        .on_transform
+        .reform(move |_: MouseEvent| (id, transform.rotated_clockwise()));
+    let on_flip = props
+        .on_transform
+        .reform(move |_: MouseEvent| (id, transform.flipped()));
+
+    if props.image.collapsed {
+        let on_expand = props.on_expand.reform(move |_: MouseEvent| id);
+        return html! {
+            <div ref={node}>
+                <div class="row align-items-center mb-2 text-muted">
+                    <div class="col-1">
+                        <img class="img-fluid" src={props.image.thumbnail.as_ref().unwrap_or(&props.image.data.url).to_string()} />
+                    </div>
+                    <div class="col-9">{format!("{}: another frame of the same burst", props.image.data.name)}</div>
+                    <div class="col-2 btn-group btn-group-sm">
+                        <button class="btn btn-outline-secondary" onclick={on_expand}>{"Show"}</button>
+                        <button class="btn btn-outline-danger" onclick={on_delete}>{"Delete"}</button>
+                    </div>
+                </div>
+            </div>
+        };
+    }
+
+    let archived = match &props.image.archive {
+        ArchiveStatus::NotUploaded | ArchiveStatus::Queued { .. } => html!(),
+        ArchiveStatus::Uploading(progress) => html! {
+            <div class="mb-3">
+                <div class="progress mb-1" role="progressbar">
+                    <div class="progress-bar" style={format!("width: {:.1}%", progress.fraction() * 100.0)}></div>
+                </div>
+                <small class="text-muted">{progress.describe()}</small>
+            </div>
+        },
+        ArchiveStatus::Archived(Some(entry)) => html! {
+            <div class="alert alert-success py-2">
+                {"Archived as "}
+                <a href={entry.download.clone()} target="_blank">{&entry.uuid}</a>
+            </div>
+        },
+        ArchiveStatus::Archived(None) => html! {
+            <div class="alert alert-success py-2">{"Archived"}</div>
+        },
+        ArchiveStatus::AlreadyArchived(existing) => {
+            let on_force_upload = props.on_upload.reform(move |_: MouseEvent| (id, true));
+            html! {
+                <div class="alert alert-secondary py-2">
+                    {"Already archived as "}
+                    <a href={existing.download.clone()} target="_blank">{&existing.uuid}</a>
+                    <button class="btn btn-sm btn-outline-secondary ms-2" onclick={on_force_upload}>{"Upload again anyway"}</button>
+                </div>
+            }
+        }
+        ArchiveStatus::Failed(why) => html! {
+            <div class="alert alert-danger py-2">{format!("Upload failed: {why}")}</div>
+        },
+    };
+    let upload_button = match &props.image.archive {
+        ArchiveStatus::NotUploaded => html! {
+            <button class="btn btn-success col mb-2" onclick={on_upload}>{"Upload to Archive"}</button>
+        },
+        ArchiveStatus::Queued { .. } => html! {
+            <button class="btn btn-success col mb-2" disabled=true>{"Waiting to upload"}</button>
+        },
+        ArchiveStatus::Uploading(_) => html! {
+            <button class="btn btn-success col mb-2" disabled=true>
+                <span class="spinner-border spinner-border-sm me-1" role="status"></span>{"Uploading..."}
+            </button>
+        },
+        ArchiveStatus::Archived(_) | ArchiveStatus::AlreadyArchived(_) => html! {
+            <button class="btn btn-outline-success col mb-2" disabled=true>{"In the archive"}</button>
+        },
+        ArchiveStatus::Failed(_) => html! {
+            <button class="btn btn-warning col mb-2" onclick={on_upload}>{"Retry upload"}</button>
+        },
+    };
+
+    let burst = match props.image.burst {
+        Some(group) => {
+            let on_keep_frame = props.on_keep_frame.reform(move |_: MouseEvent| id);
+            html! {
+                <div class="mb-2">
+                    <span class="badge text-bg-info me-2">{format!("Burst #{} of {} similar frames", group.id, group.size)}</span>
+                    <button class="btn btn-sm btn-outline-info" onclick={on_keep_frame}>{"Keep this frame, collapse the others"}</button>
+                </div>
+            }
+        }
+        None => html!(),
+    };
+
     html! {
-        <div class="row">
-            <ImageDisplayBox image_data={props.image.data.clone()} class={classes!("col-2")}/>
-            <div class="col-8">{get_analysis_result(&props.image)}</div>
-            <div class="col-2">
-                <div class="row row-cols-1">
-                    <button class="btn btn-success col mb-2" onclick={&props.on_upload}>{"Upload to Archive"}</button>
-                    <button class="btn btn-danger col mb-2" onclick={&props.on_delete}>{"Delete"}</button>
-                    <a class="btn btn-primary col mb-2" href={get_image_url(&*props.image.data)} download={props.image.data.name.clone()}>{"Download as file"}</a>
+        <div ref={node}>
+            <div class="row">
+                <div class="col-2">
+                    <ImageDisplayBox image_data={props.image.data.clone()} thumbnail={props.image.thumbnail.clone()} />
+                    <div class="btn-group btn-group-sm w-100 mt-1">
+                        <button class="btn btn-outline-secondary" title="Rotate clockwise and analyze again" onclick={on_rotate}>{"↻ Rotate"}</button>
+                        <button class="btn btn-outline-secondary" title="Mirror and analyze again" onclick={on_flip}>{"⇋ Flip"}</button>
+                    </div>
+                </div>
+                <div class="col-8">
+                    if let Some(original) = &props.image.duplicate_of {
+                        <span class="badge text-bg-warning mb-2">{format!("Same file as {original}")}</span>
+                    }
+                    {archived}
+                    {burst}
+                    {get_analysis_result(&props.image)}
+                    {get_review_select(&props.image, &props.on_review)}
+                    {get_tag_editor(&props.image, &props.on_tags, &props.on_note, &props.on_count)}
+                    {get_exif_panel(&props.image)}
+                </div>
+                <div class="col-2">
+                    <div class="row row-cols-1">
+                        {upload_button}
+                        <button class="btn btn-danger col mb-2" onclick={on_delete}>{"Delete"}</button>
+                        <a class="btn btn-primary col mb-2" href={props.image.data.url.to_string()} download={props.image.data.name.clone()}>{"Download as file"}</a>
+                    </div>
                 </div>
             </div>
         </div>
@@ -1,13 +1,16 @@
 use std::rc::Rc;
 
-use base64::Engine;
 use yew::prelude::*;
 
 use super::file_upload_box::FileDetails;
+use crate::imaging::BlobUrl;
 
 #[derive(Properties, PartialEq)]
 pub struct ImageDisplayProps {
     pub image_data: Rc<FileDetails>,
+    /// A downscaled preview to show instead of the full image, once it is ready.
+    #[prop_or_default]
+    pub thumbnail: Option<BlobUrl>,
     #[prop_or_default]
     pub class: Classes,
 }
@@ -18,20 +21,13 @@ pub fn ImageDisplayBox(props: &ImageDisplayProps) -> Html {
 }
 
 fn view_file(props: &ImageDisplayProps) -> Html {
+    let src = props.thumbnail.as_ref().unwrap_or(&props.image_data.url);
     html! {
         <div class={props.class.clone()}>
             <div class="card">
-                <img class="card-img-top" src={get_image_url(&*props.image_data)} />
+                <img class="card-img-top" src={src.to_string()} />
                 <p class="card-footer">{ format!("{}", &*props.image_data.name) }</p>
             </div>
         </div>
     }
 }
-
-pub fn get_image_url(file: &FileDetails) -> String {
-    format!(
-        "data:{};base64,{}",
-        file.file_type,
-        base64::engine::general_purpose::STANDARD.encode(&file.data)
-    )
-}
@@ -0,0 +1,130 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::{
+    Element, HtmlElement, IntersectionObserver, IntersectionObserverEntry, IntersectionObserverInit,
+};
+use yew::prelude::*;
+
+/// Space reserved for a row that has not been rendered yet, in pixels.
+const ESTIMATED_HEIGHT: i32 = 300;
+/// How far outside the viewport rows are mounted, so they are ready before they scroll in.
+const MOUNT_MARGIN: &str = "1000px 0px";
+
+type Targets = Rc<RefCell<Vec<(Element, Callback<bool>)>>>;
+
+/// One `IntersectionObserver` for all lazily mounted rows, provided to them as a context.
+#[derive(Clone)]
+pub struct LazyMountObserver(Rc<Observer>);
+
+struct Observer {
+    observer: IntersectionObserver,
+    /// The observed elements, and who to tell when each comes near the viewport or leaves it.
+    targets: Targets,
+    _callback: Closure<dyn Fn(js_sys::Array)>,
+}
+
+impl LazyMountObserver {
+    pub fn new() -> Self {
+        let targets = Targets::default();
+        let callback = {
+            let targets = targets.clone();
+            Closure::<dyn Fn(js_sys::Array)>::new(move |entries: js_sys::Array| {
+                let targets = targets.borrow();
+                for entry in entries.iter() {
+                    let entry: IntersectionObserverEntry = entry.unchecked_into();
+                    let target = entry.target();
+                    if let Some((_, on_change)) = targets.iter().find(|(e, _)| *e == target) {
+                        on_change.emit(entry.is_intersecting());
+                    }
+                }
+            })
+        };
+        let mut options = IntersectionObserverInit::new();
+        options.root_margin(MOUNT_MARGIN);
+        let observer =
+            IntersectionObserver::new_with_options(callback.as_ref().unchecked_ref(), &options)
+                .expect("failed to create IntersectionObserver");
+        Self(Rc::new(Observer {
+            observer,
+            targets,
+            _callback: callback,
+        }))
+    }
+
+    fn observe(&self, element: Element, on_change: Callback<bool>) {
+        self.0.observer.observe(&element);
+        self.0.targets.borrow_mut().push((element, on_change));
+    }
+
+    fn unobserve(&self, element: &Element) {
+        self.0.observer.unobserve(element);
+        self.0.targets.borrow_mut().retain(|(e, _)| e != element);
+    }
+}
+
+impl Default for LazyMountObserver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for LazyMountObserver {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Drop for Observer {
+    fn drop(&mut self) {
+        self.observer.disconnect();
+    }
+}
+
+/// Tracks whether the element behind `node` is near the viewport, so that long lists
+/// only mount the rows that can be seen. Needs a [`LazyMountObserver`] context.
+///
+/// Returns `None` while the content should be rendered, otherwise the height of the
+/// empty block to render in its place: the height it last had, so that unmounting it
+/// does not move the rows below.
+#[hook]
+pub fn use_lazy_mount(node: NodeRef) -> Option<i32> {
+    let observer =
+        use_context::<LazyMountObserver>().expect("use_lazy_mount needs a LazyMountObserver");
+    let visible = use_state_eq(|| false);
+    let height = use_mut_ref(|| ESTIMATED_HEIGHT);
+
+    {
+        let node = node.clone();
+        let visible = visible.clone();
+        use_effect_with_deps(
+            move |observer| {
+                let element = node.cast::<Element>();
+                if let Some(element) = &element {
+                    observer.observe(element.clone(), Callback::from(move |v| visible.set(v)));
+                }
+                let observer = observer.clone();
+                move || {
+                    if let Some(element) = element {
+                        observer.unobserve(&element);
+                    }
+                }
+            },
+            observer,
+        );
+    }
+
+    {
+        let height = height.clone();
+        let visible = *visible;
+        use_effect(move || {
+            if let Some(element) = node.cast::<HtmlElement>().filter(|_| visible) {
+                *height.borrow_mut() = element.offset_height();
+            }
+        });
+    }
+
+    let placeholder = (!*visible).then(|| *height.borrow());
+    placeholder
+}
@@ -1,6 +1,15 @@
 pub mod alert;
+pub mod csv_options;
+pub mod dataset_options;
+pub mod downscale_options;
 pub mod file_upload_box;
+pub mod form;
 pub mod image_analysis_row;
 pub mod image_display;
 pub mod layout;
+pub mod lazy_mount;
 pub mod nav;
+pub mod privacy_options;
+pub mod reanalysis_diff;
+pub mod session_info;
+pub mod tag_cloud;
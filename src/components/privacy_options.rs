@@ -0,0 +1,58 @@
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use crate::privacy::{LocationPolicy, PrivacyOptions};
+
+#[derive(Properties, PartialEq)]
+pub struct PrivacyOptionsProps {
+    pub options: PrivacyOptions,
+    pub on_change: Callback<PrivacyOptions>,
+}
+
+#[function_component]
+pub fn PrivacyOptionsForm(props: &PrivacyOptionsProps) -> Html {
+    let on_location = {
+        let options = props.options.clone();
+        props.on_change.reform(move |ev: Event| {
+            let select: HtmlSelectElement = ev.target_unchecked_into();
+            let location = select
+                .value()
+                .parse()
+                .ok()
+                .and_then(|i: usize| LocationPolicy::ALL.get(i).copied())
+                .unwrap_or(options.location);
+            PrivacyOptions {
+                location,
+                ..options.clone()
+            }
+        })
+    };
+    let on_strip_personal = {
+        let options = props.options.clone();
+        props.on_change.reform(move |ev: Event| {
+            let input: HtmlInputElement = ev.target_unchecked_into();
+            PrivacyOptions {
+                strip_personal: input.checked(),
+                ..options.clone()
+            }
+        })
+    };
+
+    html! {
+        <div class="row g-2 align-items-center">
+            <div class="col-4">
+                <select class="form-select" onchange={on_location}>
+                    { for LocationPolicy::ALL.iter().enumerate().map(|(i, policy)| html!(
+                        <option value={i.to_string()} selected={*policy == props.options.location}>{policy.label()}</option>
+                    )) }
+                </select>
+            </div>
+            <div class="col-auto">
+                <div class="form-check">
+                    <input class="form-check-input" type="checkbox" id="privacy-strip-personal" checked={props.options.strip_personal} onchange={on_strip_personal} />
+                    <label class="form-check-label" for="privacy-strip-personal">{"Remove names, serial numbers, comments, XMP and IPTC"}</label>
+                </div>
+            </div>
+        </div>
+    }
+}
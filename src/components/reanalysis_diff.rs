@@ -0,0 +1,138 @@
+use std::{collections::BTreeSet, rc::Rc};
+
+use yew::prelude::*;
+
+use crate::api::SearchResult;
+use crate::views::image_analysis::ImageAnalysisData;
+
+/// An archived image that was sent through the classifier again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReanalysisEntry {
+    pub item: SearchResult,
+    pub old: Option<ImageAnalysisData>,
+    pub new: Result<ImageAnalysisData, String>,
+}
+
+impl ReanalysisEntry {
+    fn old_top(&self) -> Option<(&str, f64)> {
+        self.old.as_ref().and_then(|d| d.top_class())
+    }
+
+    fn new_top(&self) -> Option<(&str, f64)> {
+        self.new.as_ref().ok().and_then(|d| d.top_class())
+    }
+
+    /// Whether the predicted label is different now.
+    pub fn label_changed(&self) -> bool {
+        match (self.old_top(), self.new_top()) {
+            (Some((old, _)), Some((new, _))) => old != new,
+            (None, Some(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// The image's tags with the old predicted label swapped for the new one,
+    /// or `None` if that would not change anything.
+    pub fn retagged(&self) -> Option<Vec<String>> {
+        let (new, _) = self.new_top()?;
+        let mut tags: Vec<String> = self.item.tags.clone();
+        if let Some((old, _)) = self.old_top() {
+            tags.retain(|t| t != old);
+        }
+        if !tags.iter().any(|t| t == new) {
+            tags.push(new.to_string());
+        }
+        (tags != self.item.tags).then_some(tags)
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ReanalysisDiffProps {
+    pub entries: Rc<Vec<ReanalysisEntry>>,
+    /// Called with the UUIDs of the images whose tags should follow the new labels.
+    pub on_update_tags: Callback<Vec<String>>,
+    pub on_close: Callback<MouseEvent>,
+}
+
+fn format_top(top: Option<(&str, f64)>) -> Html {
+    match top {
+        Some((class, score)) => {
+            html!(<>{class}<span class="text-muted">{format!(" ({score:.3})")}</span></>)
+        }
+        None => html!(<span class="text-muted">{"—"}</span>),
+    }
+}
+
+#[function_component]
+pub fn ReanalysisDiff(props: &ReanalysisDiffProps) -> Html {
+    let changed = props.entries.iter().filter(|e| e.label_changed()).count();
+    let retaggable: Vec<String> = props
+        .entries
+        .iter()
+        .filter(|e| e.retagged().is_some())
+        .map(|e| e.item.uuid.clone())
+        .collect();
+
+    let rows = props.entries.iter().map(|entry| {
+        let deltas = match (&entry.old, &entry.new) {
+            (_, Err(why)) => html!(<span class="text-danger">{why}</span>),
+            (None, Ok(_)) => html!(<span class="text-muted">{"No stored analysis"}</span>),
+            (Some(old), Ok(new)) => {
+                let classes: BTreeSet<&String> = old
+                    .overall_classification
+                    .keys()
+                    .chain(new.overall_classification.keys())
+                    .collect();
+                let deltas = classes.into_iter().map(|class| {
+                    let before = old.overall_classification.get(class).copied().unwrap_or(0.0);
+                    let after = new.overall_classification.get(class).copied().unwrap_or(0.0);
+                    let delta = after - before;
+                    let style = if delta > 0.0 { "text-success" } else if delta < 0.0 { "text-danger" } else { "text-muted" };
+                    html!(<span class={classes!("me-2", style)}>{format!("{class}: {delta:+.3}")}</span>)
+                });
+                html!({ for deltas })
+            }
+        };
+        let update = match entry.retagged() {
+            Some(tags) => {
+                let uuid = entry.item.uuid.clone();
+                let onclick = props.on_update_tags.reform(move |_: MouseEvent| vec![uuid.clone()]);
+                html!(<button class="btn btn-sm btn-outline-primary" title={tags.join(" ")} {onclick}>{"Update tags"}</button>)
+            }
+            None => html!(),
+        };
+
+        html! {
+            <tr class={classes!(entry.label_changed().then_some("table-warning"))}>
+                <td>{&entry.item.filename}</td>
+                <td>{format_top(entry.old_top())}</td>
+                <td>{format_top(entry.new_top())}</td>
+                <td>{deltas}</td>
+                <td>{update}</td>
+            </tr>
+        }
+    });
+
+    let update_all = {
+        let retaggable = retaggable.clone();
+        props
+            .on_update_tags
+            .reform(move |_: MouseEvent| retaggable.clone())
+    };
+
+    html! {
+        <div class="card mb-3">
+            <div class="card-header d-flex justify-content-between align-items-center">
+                <span>{format!("Re-analyzed {} images, {changed} with a different label", props.entries.len())}</span>
+                <div>
+                    <button class="btn btn-sm btn-primary me-2" disabled={retaggable.is_empty()} onclick={update_all}>{format!("Update tags of {} images", retaggable.len())}</button>
+                    <button type="button" class="btn-close" onclick={&props.on_close} />
+                </div>
+            </div>
+            <table class="table table-striped mb-0">
+                <thead><tr><th>{"File"}</th><th>{"Stored label"}</th><th>{"New label"}</th><th>{"Score change"}</th><th></th></tr></thead>
+                <tbody>{ for rows }</tbody>
+            </table>
+        </div>
+    }
+}
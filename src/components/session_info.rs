@@ -0,0 +1,48 @@
+use yew::prelude::*;
+
+use crate::components::form::on_input;
+use crate::session::SessionInfo;
+
+#[derive(Properties, PartialEq)]
+pub struct SessionInfoProps {
+    pub info: SessionInfo,
+    pub on_change: Callback<SessionInfo>,
+}
+
+fn coordinate(value: Option<f64>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+#[function_component]
+pub fn SessionInfoForm(props: &SessionInfoProps) -> Html {
+    let info = &props.info;
+    html! {
+        <div class="row g-2 align-items-end">
+            <div class="col-3">
+                <label class="form-label">{"Observer"}</label>
+                <input class="form-control" type="text" value={info.observer.clone()}
+                    onchange={on_input(&props.info, &props.on_change, |i, v| i.observer = v)} />
+            </div>
+            <div class="col-3">
+                <label class="form-label">{"Site"}</label>
+                <input class="form-control" type="text" value={info.site.clone()}
+                    onchange={on_input(&props.info, &props.on_change, |i, v| i.site = v)} />
+            </div>
+            <div class="col-2">
+                <label class="form-label">{"Latitude"}</label>
+                <input class="form-control" type="number" min="-90" max="90" step="any" value={coordinate(info.latitude)}
+                    onchange={on_input(&props.info, &props.on_change, |i, v: String| i.latitude = v.parse().ok().filter(|l: &f64| l.abs() <= 90.0))} />
+            </div>
+            <div class="col-2">
+                <label class="form-label">{"Longitude"}</label>
+                <input class="form-control" type="number" min="-180" max="180" step="any" value={coordinate(info.longitude)}
+                    onchange={on_input(&props.info, &props.on_change, |i, v: String| i.longitude = v.parse().ok().filter(|l: &f64| l.abs() <= 180.0))} />
+            </div>
+            <div class="col-2">
+                <label class="form-label">{"Date"}</label>
+                <input class="form-control" type="date" value={info.date.clone()}
+                    onchange={on_input(&props.info, &props.on_change, |i, v| i.date = v)} />
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,62 @@
+use std::{collections::HashMap, rc::Rc};
+
+use yew::prelude::*;
+
+pub type TagCounts = HashMap<String, u64>;
+
+#[derive(Properties, PartialEq)]
+pub struct TagCloudProps {
+    pub tags: Rc<TagCounts>,
+    /// Tags that are already part of the query; these are highlighted.
+    #[prop_or_default]
+    pub selected: Vec<String>,
+    pub on_click: Callback<String>,
+}
+
+const MIN_FONT_REM: f64 = 0.8;
+const MAX_FONT_REM: f64 = 2.0;
+
+#[function_component]
+pub fn TagCloud(props: &TagCloudProps) -> Html {
+    if props.tags.is_empty() {
+        return html!(<p class="text-muted">{"The archive has no tags yet."}</p>);
+    }
+
+    let mut tags: Vec<(&String, &u64)> = props.tags.iter().collect();
+    tags.sort_by_key(|(tag, _)| *tag);
+
+    // Scale logarithmically, otherwise one very common tag makes all the others tiny.
+    let max = tags.iter().map(|(_, c)| **c).max().unwrap_or(1).max(1) as f64;
+    let font_size = |count: u64| {
+        let ratio = if max > 1.0 {
+            (count.max(1) as f64).ln() / max.ln()
+        } else {
+            1.0
+        };
+        MIN_FONT_REM + (MAX_FONT_REM - MIN_FONT_REM) * ratio
+    };
+
+    let items = tags.into_iter().map(|(tag, count)| {
+        let is_selected = props.selected.contains(tag);
+        let onclick = {
+            let tag = tag.clone();
+            props.on_click.reform(move |_: MouseEvent| tag.clone())
+        };
+        html! {
+            <button type="button"
+                class={classes!("btn", "btn-link", "p-1", if is_selected {"link-success"} else {"link-body-emphasis"})}
+                style={format!("font-size: {:.2}rem", font_size(*count))}
+                title={format!("{count} images")}
+                disabled={is_selected}
+                {onclick}>
+                {tag}<sup class="text-muted ms-1">{count}</sup>
+            </button>
+        }
+    });
+
+    html! {
+        <div class="d-flex flex-wrap align-items-baseline">
+            { for items }
+        </div>
+    }
+}
@@ -0,0 +1,25 @@
+//! Writing CSV rows as described in RFC 4180.
+
+/// Append one record to `out`, quoting the fields that need it and ending the line with CRLF.
+pub fn write_record<S: AsRef<str>>(out: &mut String, fields: &[S], delimiter: char) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(delimiter);
+        }
+        write_field(out, field.as_ref(), delimiter);
+    }
+    out.push_str("\r\n");
+}
+
+fn write_field(out: &mut String, field: &str, delimiter: char) {
+    let needs_quotes = field
+        .chars()
+        .any(|c| c == delimiter || c == '"' || c == '\n' || c == '\r');
+    if needs_quotes {
+        out.push('"');
+        out.push_str(&field.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(field);
+    }
+}
@@ -0,0 +1,23 @@
+use gloo::file::{Blob, ObjectUrl};
+use gloo::timers::callback::Timeout;
+use wasm_bindgen::JsCast;
+use web_sys::HtmlAnchorElement;
+
+/// How long to keep an object URL alive after the download was started.
+/// Browsers fetch the URL asynchronously after the click, so it can't be revoked right away.
+const REVOKE_DELAY_MS: u32 = 60_000;
+
+/// Make the browser save the blob as a file with the given name.
+pub fn download_blob(blob: Blob, file_name: &str) {
+    let url = ObjectUrl::from(blob);
+    let anchor: HtmlAnchorElement = gloo::utils::document()
+        .create_element("a")
+        .expect("failed to create anchor element")
+        .unchecked_into();
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+
+    // Dropping the last clone of the `ObjectUrl` revokes it.
+    Timeout::new(REVOKE_DELAY_MS, move || drop(url)).forget();
+}
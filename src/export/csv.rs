@@ -0,0 +1,302 @@
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+use gloo::file::Blob;
+use yew::prelude::*;
+
+use crate::components::csv_options::CsvOptionsForm;
+use crate::csv::write_record;
+use crate::views::image_analysis::ImageAnalysisOutcome;
+
+use super::{iso_timestamp, ExportOutput, ExportSession, Exporter};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CsvColumn {
+    Name,
+    Status,
+    PredictedClass,
+    PredictedScore,
+    /// One column per class seen anywhere in the session.
+    ClassScores,
+    ReviewedLabel,
+    Error,
+    FileType,
+    FileSize,
+    Sha256,
+    AddedAt,
+    AnalyzedAt,
+    CapturedAt,
+    Latitude,
+    Longitude,
+    Camera,
+    BurstGroup,
+    Count,
+    Observer,
+    Site,
+    SiteLatitude,
+    SiteLongitude,
+    SessionDate,
+}
+
+impl CsvColumn {
+    pub const ALL: [CsvColumn; 23] = [
+        CsvColumn::Name,
+        CsvColumn::Status,
+        CsvColumn::PredictedClass,
+        CsvColumn::PredictedScore,
+        CsvColumn::ClassScores,
+        CsvColumn::ReviewedLabel,
+        CsvColumn::Error,
+        CsvColumn::FileType,
+        CsvColumn::FileSize,
+        CsvColumn::Sha256,
+        CsvColumn::AddedAt,
+        CsvColumn::AnalyzedAt,
+        CsvColumn::CapturedAt,
+        CsvColumn::Latitude,
+        CsvColumn::Longitude,
+        CsvColumn::Camera,
+        CsvColumn::BurstGroup,
+        CsvColumn::Count,
+        CsvColumn::Observer,
+        CsvColumn::Site,
+        CsvColumn::SiteLatitude,
+        CsvColumn::SiteLongitude,
+        CsvColumn::SessionDate,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CsvColumn::Name => "File name",
+            CsvColumn::Status => "Status",
+            CsvColumn::PredictedClass => "Predicted class",
+            CsvColumn::PredictedScore => "Predicted class score",
+            CsvColumn::ClassScores => "Score of every class",
+            CsvColumn::ReviewedLabel => "Reviewed label",
+            CsvColumn::Error => "Error text",
+            CsvColumn::FileType => "MIME type",
+            CsvColumn::FileSize => "File size (bytes)",
+            CsvColumn::Sha256 => "SHA-256",
+            CsvColumn::AddedAt => "Added at",
+            CsvColumn::AnalyzedAt => "Analyzed at",
+            CsvColumn::CapturedAt => "Photo taken at",
+            CsvColumn::Latitude => "Latitude",
+            CsvColumn::Longitude => "Longitude",
+            CsvColumn::Camera => "Camera",
+            CsvColumn::BurstGroup => "Burst group",
+            CsvColumn::Count => "Individuals",
+            CsvColumn::Observer => "Observer",
+            CsvColumn::Site => "Site",
+            CsvColumn::SiteLatitude => "Site latitude",
+            CsvColumn::SiteLongitude => "Site longitude",
+            CsvColumn::SessionDate => "Session date",
+        }
+    }
+
+    fn header(&self) -> &'static str {
+        match self {
+            CsvColumn::Name => "name",
+            CsvColumn::Status => "status",
+            CsvColumn::PredictedClass => "class",
+            CsvColumn::PredictedScore => "score",
+            CsvColumn::ClassScores => "score_",
+            CsvColumn::ReviewedLabel => "reviewed_class",
+            CsvColumn::Error => "error",
+            CsvColumn::FileType => "file_type",
+            CsvColumn::FileSize => "file_size",
+            CsvColumn::Sha256 => "sha256",
+            CsvColumn::AddedAt => "added_at",
+            CsvColumn::AnalyzedAt => "analyzed_at",
+            CsvColumn::CapturedAt => "captured_at",
+            CsvColumn::Latitude => "latitude",
+            CsvColumn::Longitude => "longitude",
+            CsvColumn::Camera => "camera",
+            CsvColumn::BurstGroup => "burst_group",
+            CsvColumn::Count => "count",
+            CsvColumn::Observer => "observer",
+            CsvColumn::Site => "site",
+            CsvColumn::SiteLatitude => "site_latitude",
+            CsvColumn::SiteLongitude => "site_longitude",
+            CsvColumn::SessionDate => "session_date",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CsvOptions {
+    /// Columns in output order.
+    pub columns: Vec<CsvColumn>,
+    pub delimiter: char,
+    pub decimal_separator: char,
+}
+
+impl Default for CsvOptions {
+    /// Same layout as the original labels-only export.
+    fn default() -> Self {
+        Self {
+            columns: vec![CsvColumn::Name, CsvColumn::PredictedClass],
+            delimiter: ';',
+            decimal_separator: '.',
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Every column, for manifests bundled with other exports.
+    pub fn all_columns() -> Self {
+        Self {
+            columns: CsvColumn::ALL.to_vec(),
+            ..Self::default()
+        }
+    }
+
+    fn number(&self, value: f64) -> String {
+        let s = value.to_string();
+        if self.decimal_separator == '.' {
+            s
+        } else {
+            s.replace('.', &self.decimal_separator.to_string())
+        }
+    }
+}
+
+pub fn status_name(outcome: &ImageAnalysisOutcome) -> &'static str {
+    match outcome {
+        ImageAnalysisOutcome::WaitingToSend => "waiting_to_send",
+        ImageAnalysisOutcome::WaitingForResponse(_) => "waiting_for_response",
+        ImageAnalysisOutcome::Analyzed(_) => "analyzed",
+        ImageAnalysisOutcome::Error(_) => "error",
+    }
+}
+
+/// Write the session as CSV with the chosen columns and separators.
+pub fn session_csv(session: &ExportSession, options: &CsvOptions) -> String {
+    let (images, info) = (session.images, session.info);
+    let classes: BTreeSet<&String> = images
+        .iter()
+        .filter_map(|img| match &img.outcome {
+            ImageAnalysisOutcome::Analyzed(res) => Some(res.overall_classification.keys()),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+
+    let mut out = String::new();
+    let mut header = vec![];
+    for column in options.columns.iter() {
+        match column {
+            CsvColumn::ClassScores => {
+                header.extend(classes.iter().map(|c| format!("{}{c}", column.header())))
+            }
+            _ => header.push(column.header().to_string()),
+        }
+    }
+    write_record(&mut out, &header, options.delimiter);
+
+    for img in images {
+        let analysis = match &img.outcome {
+            ImageAnalysisOutcome::Analyzed(res) => Some(res),
+            _ => None,
+        };
+        let top = analysis.and_then(|res| res.top_class());
+        let mut fields = vec![];
+        for column in options.columns.iter() {
+            match column {
+                CsvColumn::Name => fields.push(img.data.name.clone()),
+                CsvColumn::Status => fields.push(status_name(&img.outcome).to_string()),
+                CsvColumn::PredictedClass => {
+                    fields.push(top.map_or("unknown", |(class, _)| class).to_string())
+                }
+                CsvColumn::PredictedScore => fields.push(
+                    top.map(|(_, score)| options.number(score))
+                        .unwrap_or_default(),
+                ),
+                CsvColumn::ClassScores => fields.extend(classes.iter().map(|class| {
+                    analysis
+                        .and_then(|res| res.overall_classification.get(*class))
+                        .map(|score| options.number(*score))
+                        .unwrap_or_default()
+                })),
+                CsvColumn::ReviewedLabel => {
+                    fields.push(img.reviewed_label.clone().unwrap_or_default())
+                }
+                CsvColumn::Error => fields.push(match &img.outcome {
+                    ImageAnalysisOutcome::Error(why) => why.clone(),
+                    _ => String::new(),
+                }),
+                CsvColumn::FileType => fields.push(img.data.file_type.clone()),
+                CsvColumn::FileSize => fields.push(img.data.size().to_string()),
+                CsvColumn::Sha256 => fields.push(img.data.sha256.clone()),
+                CsvColumn::AddedAt => fields.push(iso_timestamp(img.added_at)),
+                CsvColumn::AnalyzedAt => {
+                    fields.push(img.analyzed_at.map(iso_timestamp).unwrap_or_default())
+                }
+                CsvColumn::CapturedAt => {
+                    fields.push(img.data.exif.date_time_original.clone().unwrap_or_default())
+                }
+                CsvColumn::Latitude => fields.push(
+                    img.data
+                        .exif
+                        .latitude
+                        .map(|v| options.number(v))
+                        .unwrap_or_default(),
+                ),
+                CsvColumn::Longitude => fields.push(
+                    img.data
+                        .exif
+                        .longitude
+                        .map(|v| options.number(v))
+                        .unwrap_or_default(),
+                ),
+                CsvColumn::Camera => fields.push(img.data.exif.camera().unwrap_or_default()),
+                CsvColumn::BurstGroup => fields.push(
+                    img.burst
+                        .map(|group| group.id.to_string())
+                        .unwrap_or_default(),
+                ),
+                CsvColumn::Count => fields.push(img.count.to_string()),
+                CsvColumn::Observer => fields.push(info.observer.clone()),
+                CsvColumn::Site => fields.push(info.site.clone()),
+                CsvColumn::SiteLatitude => {
+                    fields.push(info.latitude.map(|v| options.number(v)).unwrap_or_default())
+                }
+                CsvColumn::SiteLongitude => fields.push(
+                    info.longitude
+                        .map(|v| options.number(v))
+                        .unwrap_or_default(),
+                ),
+                CsvColumn::SessionDate => fields.push(info.date.clone()),
+            }
+        }
+        write_record(&mut out, &fields, options.delimiter);
+    }
+    out
+}
+
+#[derive(Default)]
+pub struct CsvExporter(pub CsvOptions);
+
+impl Exporter for CsvExporter {
+    fn name(&self) -> &'static str {
+        "Table as CSV"
+    }
+
+    fn file_extension(&self) -> &'static str {
+        "csv"
+    }
+
+    fn mime_type(&self) -> &'static str {
+        "text/csv"
+    }
+
+    fn write(&self, session: &ExportSession) -> ExportOutput {
+        let csv = session_csv(session, &self.0);
+        ExportOutput::new(Blob::new_with_options(csv.as_str(), Some(self.mime_type())))
+    }
+
+    fn view_options(&self, on_change: Callback<Rc<dyn Exporter>>) -> Html {
+        let on_change =
+            on_change.reform(|options| Rc::new(CsvExporter(options)) as Rc<dyn Exporter>);
+        html!(<CsvOptionsForm options={self.0.clone()} {on_change} />)
+    }
+}
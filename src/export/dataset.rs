@@ -0,0 +1,393 @@
+//! Training dataset export: a stratified train/validation/test split of the session.
+
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use gloo::file::Blob;
+use yew::prelude::*;
+
+use crate::components::dataset_options::DatasetOptionsForm;
+use crate::csv::write_record;
+use crate::views::image_analysis::{ImageAnalysisOutcome, ImageAnalysisStatus, ImageId};
+use crate::zip::{sanitize_path_component, ZipWriter};
+
+use super::{ExportOutput, ExportSession, Exporter};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Split {
+    Train,
+    Validation,
+    Test,
+}
+
+impl Split {
+    pub const ALL: [Split; 3] = [Split::Train, Split::Validation, Split::Test];
+
+    pub fn folder(&self) -> &'static str {
+        match self {
+            Split::Train => "train",
+            Split::Validation => "val",
+            Split::Test => "test",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DatasetFormat {
+    /// `<split>/<class>/<file>` folders, as read by torchvision's `ImageFolder`, plus a manifest.
+    ImageFolderZip,
+    /// Only the manifest, for images that are already stored elsewhere.
+    CsvManifest,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DatasetOptions {
+    /// Relative sizes of the train, validation and test partitions.
+    pub ratio: [u32; 3],
+    pub seed: u64,
+    /// Predictions below this confidence are left out unless they were reviewed.
+    pub min_confidence: f64,
+    pub reviewed_only: bool,
+    pub format: DatasetFormat,
+}
+
+impl Default for DatasetOptions {
+    fn default() -> Self {
+        Self {
+            ratio: [70, 15, 15],
+            seed: 42,
+            min_confidence: 0.8,
+            reviewed_only: false,
+            format: DatasetFormat::ImageFolderZip,
+        }
+    }
+}
+
+/// SplitMix64, so that the same seed gives the same split in every browser.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+/// The label an image gets in the dataset, or `None` if it should be left out.
+fn dataset_label<'a>(img: &'a ImageAnalysisStatus, options: &DatasetOptions) -> Option<&'a str> {
+    if let Some(label) = &img.reviewed_label {
+        return Some(label);
+    }
+    if options.reviewed_only {
+        return None;
+    }
+    match &img.outcome {
+        ImageAnalysisOutcome::Analyzed(res) => res
+            .top_class()
+            .filter(|(_, score)| *score >= options.min_confidence)
+            .map(|(class, _)| class),
+        _ => None,
+    }
+}
+
+pub struct DatasetEntry<'a> {
+    pub image: &'a ImageAnalysisStatus,
+    pub label: &'a str,
+    pub split: Split,
+}
+
+/// What decides which partition an image goes to.
+struct SplitKey<'a> {
+    label: &'a str,
+    sha256: &'a str,
+    burst: Option<ImageId>,
+}
+
+/// Find the representative of `i`'s set in a union-find forest.
+fn find_root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// Assign items to partitions, keeping the class proportions in each. Copies of a file
+/// and frames of one burst are assigned together, so that none of them is trained on
+/// and then tested on. Returns `(index, split)` pairs, grouped by class.
+fn assign_splits(items: &[SplitKey], options: &DatasetOptions) -> Vec<(usize, Split)> {
+    // Link each item to the first one with the same hash or in the same burst.
+    let mut parent: Vec<usize> = (0..items.len()).collect();
+    let mut first_by_sha256: HashMap<&str, usize> = HashMap::new();
+    let mut first_by_burst: HashMap<ImageId, usize> = HashMap::new();
+    for (i, item) in items.iter().enumerate() {
+        let mut firsts = vec![*first_by_sha256.entry(item.sha256).or_insert(i)];
+        if let Some(burst) = item.burst {
+            firsts.push(*first_by_burst.entry(burst).or_insert(i));
+        }
+        for first in firsts {
+            let (a, b) = (find_root(&mut parent, i), find_root(&mut parent, first));
+            parent[a] = b;
+        }
+    }
+
+    let mut by_class: BTreeMap<&str, BTreeMap<usize, Vec<usize>>> = BTreeMap::new();
+    for (i, item) in items.iter().enumerate() {
+        let root = find_root(&mut parent, i);
+        by_class
+            .entry(item.label)
+            .or_default()
+            .entry(root)
+            .or_default()
+            .push(i);
+    }
+
+    let total: u32 = options.ratio.iter().sum::<u32>().max(1);
+    let mut rng = SplitMix64(options.seed);
+    // A group that spans classes keeps the partition it got in the first one.
+    let mut group_splits: HashMap<usize, Split> = HashMap::new();
+    let mut assigned = vec![];
+    for groups in by_class.into_values() {
+        let n: usize = groups.values().map(Vec::len).sum();
+        let mut groups: Vec<Vec<usize>> = groups.into_values().collect();
+        // Sort first, so the split only depends on the seed and not on the order images were dropped in.
+        for group in groups.iter_mut() {
+            group.sort_by_key(|&i| items[i].sha256);
+        }
+        groups.sort_by_key(|group| items[group[0]].sha256);
+        rng.shuffle(&mut groups);
+
+        let train = (n as f64 * options.ratio[0] as f64 / total as f64).round() as usize;
+        let val = (n as f64 * options.ratio[1] as f64 / total as f64).round() as usize;
+        let mut counts = [0; 3];
+        for group in groups {
+            let root = find_root(&mut parent, group[0]);
+            let split = *group_splits.entry(root).or_insert_with(|| {
+                if counts[Split::Train as usize] < train {
+                    Split::Train
+                } else if counts[Split::Validation as usize] < val {
+                    Split::Validation
+                } else {
+                    Split::Test
+                }
+            });
+            counts[split as usize] += group.len();
+            assigned.extend(group.into_iter().map(|i| (i, split)));
+        }
+    }
+    assigned
+}
+
+/// Assign every eligible image to a partition, keeping the class proportions in each.
+pub fn split_dataset<'a>(
+    images: &'a [ImageAnalysisStatus],
+    options: &DatasetOptions,
+) -> Vec<DatasetEntry<'a>> {
+    let eligible: Vec<(&ImageAnalysisStatus, &str)> = images
+        .iter()
+        .filter_map(|img| Some((img, dataset_label(img, options)?)))
+        .collect();
+    let keys: Vec<SplitKey> = eligible
+        .iter()
+        .map(|(img, label)| SplitKey {
+            label,
+            sha256: &img.data.sha256,
+            burst: img.burst.map(|b| b.id),
+        })
+        .collect();
+    assign_splits(&keys, options)
+        .into_iter()
+        .map(|(i, split)| DatasetEntry {
+            image: eligible[i].0,
+            label: eligible[i].1,
+            split,
+        })
+        .collect()
+}
+
+fn entry_path(entry: &DatasetEntry) -> String {
+    format!(
+        "{}/{}/{}",
+        entry.split.folder(),
+        sanitize_path_component(entry.label),
+        sanitize_path_component(&entry.image.data.name)
+    )
+}
+
+fn manifest_row(out: &mut String, path: &str, entry: &DatasetEntry) {
+    let source = if entry.image.reviewed_label.is_some() {
+        "reviewed"
+    } else {
+        "predicted"
+    };
+    write_record(
+        out,
+        &[
+            path,
+            entry.split.folder(),
+            entry.label,
+            &entry.image.data.name,
+            &entry.image.data.sha256,
+            source,
+        ],
+        ',',
+    );
+}
+
+const MANIFEST_HEADER: [&str; 6] = ["path", "split", "class", "name", "sha256", "label_source"];
+
+/// Build the dataset file; also returns how many images went into each partition.
+pub fn session_dataset(
+    images: &[ImageAnalysisStatus],
+    options: &DatasetOptions,
+) -> (Blob, [usize; 3]) {
+    let entries = split_dataset(images, options);
+    let mut counts = [0; 3];
+    for entry in entries.iter() {
+        counts[entry.split as usize] += 1;
+    }
+
+    let mut manifest = String::new();
+    write_record(&mut manifest, &MANIFEST_HEADER, ',');
+    match options.format {
+        DatasetFormat::CsvManifest => {
+            for entry in entries.iter() {
+                manifest_row(&mut manifest, &entry_path(entry), entry);
+            }
+            let blob = Blob::new_with_options(manifest.as_str(), Some("text/csv"));
+            (blob, counts)
+        }
+        DatasetFormat::ImageFolderZip => {
+            let mut zip = ZipWriter::new();
+            for entry in entries.iter() {
+                let image = &entry.image.data;
+                let path = zip.add_blob(&entry_path(entry), &image.blob, image.crc32);
+                manifest_row(&mut manifest, &path, entry);
+            }
+            zip.add_file("manifest.csv", manifest.as_bytes());
+            (zip.finish(), counts)
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DatasetExporter(pub DatasetOptions);
+
+impl Exporter for DatasetExporter {
+    fn name(&self) -> &'static str {
+        "Training dataset (train/val/test split)"
+    }
+
+    fn file_stem(&self) -> &'static str {
+        "dataset"
+    }
+
+    fn file_extension(&self) -> &'static str {
+        match self.0.format {
+            DatasetFormat::ImageFolderZip => "zip",
+            DatasetFormat::CsvManifest => "csv",
+        }
+    }
+
+    fn mime_type(&self) -> &'static str {
+        match self.0.format {
+            DatasetFormat::ImageFolderZip => "application/zip",
+            DatasetFormat::CsvManifest => "text/csv",
+        }
+    }
+
+    fn write(&self, session: &ExportSession) -> ExportOutput {
+        let (data, [train, val, test]) = session_dataset(session.images, &self.0);
+        let excluded = session.images.len() - train - val - test;
+        ExportOutput::new(data).with_summary(format!("Training dataset: {train} train, {val} validation, {test} test; {excluded} uncertain, unreviewed or failed images left out."))
+    }
+
+    fn view_options(&self, on_change: Callback<Rc<dyn Exporter>>) -> Html {
+        let on_change =
+            on_change.reform(|options| Rc::new(DatasetExporter(options)) as Rc<dyn Exporter>);
+        html!(<DatasetOptionsForm options={self.0.clone()} {on_change} />)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sha256(n: usize) -> String {
+        format!("{n:064x}")
+    }
+
+    fn keys<'a>(shas: &'a [String], bursts: &[Option<ImageId>]) -> Vec<SplitKey<'a>> {
+        shas.iter()
+            .zip(bursts)
+            .map(|(sha256, burst)| SplitKey {
+                label: "fox",
+                sha256,
+                burst: *burst,
+            })
+            .collect()
+    }
+
+    fn split_of(assigned: &[(usize, Split)], i: usize) -> Split {
+        assigned.iter().find(|(j, _)| *j == i).unwrap().1
+    }
+
+    #[test]
+    fn copies_share_a_split() {
+        let mut shas: Vec<String> = (0..20).map(sha256).collect();
+        shas.push(sha256(7));
+        let items = keys(&shas, &[None; 21]);
+        for seed in 0..50 {
+            let options = DatasetOptions {
+                seed,
+                ..Default::default()
+            };
+            let assigned = assign_splits(&items, &options);
+            assert_eq!(assigned.len(), items.len());
+            assert_eq!(
+                split_of(&assigned, 7),
+                split_of(&assigned, 20),
+                "seed {seed}"
+            );
+        }
+    }
+
+    #[test]
+    fn burst_frames_share_a_split() {
+        let shas: Vec<String> = (0..20).map(sha256).collect();
+        let mut bursts = [None; 20];
+        bursts[3..6].fill(Some(3));
+        let items = keys(&shas, &bursts);
+        for seed in 0..50 {
+            let options = DatasetOptions {
+                seed,
+                ..Default::default()
+            };
+            let assigned = assign_splits(&items, &options);
+            let split = split_of(&assigned, 3);
+            assert_eq!(split_of(&assigned, 4), split, "seed {seed}");
+            assert_eq!(split_of(&assigned, 5), split, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn singletons_keep_the_ratio() {
+        let shas: Vec<String> = (0..20).map(sha256).collect();
+        let items = keys(&shas, &[None; 20]);
+        let mut counts = [0; 3];
+        for (_, split) in assign_splits(&items, &DatasetOptions::default()) {
+            counts[split as usize] += 1;
+        }
+        assert_eq!(counts, [14, 3, 3]);
+    }
+}
@@ -0,0 +1,214 @@
+//! Darwin Core Archive export, for publishing survey results to GBIF-style databases.
+//!
+//! See <https://dwc.tdwg.org/text/> for the archive layout.
+
+use std::collections::HashSet;
+
+use gloo::file::Blob;
+
+use crate::session::SessionInfo;
+use crate::views::image_analysis::{ImageAnalysisOutcome, ImageAnalysisStatus};
+use crate::zip::ZipWriter;
+
+use super::{iso_timestamp, ExportOutput, ExportSession, Exporter};
+
+const DWC: &str = "http://rs.tdwg.org/dwc/terms/";
+
+/// Occurrence columns, in order; the first one is the record ID.
+const FIELDS: [&str; 17] = [
+    "occurrenceID",
+    "basisOfRecord",
+    "individualCount",
+    "scientificName",
+    "vernacularName",
+    "kingdom",
+    "genus",
+    "eventDate",
+    "recordedBy",
+    "locality",
+    "decimalLatitude",
+    "decimalLongitude",
+    "geodeticDatum",
+    "associatedMedia",
+    "identifiedBy",
+    "identificationVerificationStatus",
+    "identificationRemarks",
+];
+
+/// Map a classifier label to its scientific name.
+pub fn scientific_name(class: &str) -> Option<&'static str> {
+    match class.to_lowercase().as_str() {
+        "шипун" | "mute swan" | "cygnus olor" => Some("Cygnus olor"),
+        "кликун" | "whooper swan" | "cygnus cygnus" => Some("Cygnus cygnus"),
+        "малый" | "bewick's swan" | "cygnus columbianus bewickii" => {
+            Some("Cygnus columbianus bewickii")
+        }
+        _ => None,
+    }
+}
+
+/// Tabs and newlines can't be escaped in the archive, so they become spaces.
+fn clean(value: &str) -> String {
+    value.replace(['\t', '\n', '\r'], " ")
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Photo metadata is preferred for the date and place; the session details fill the gaps.
+fn occurrence_row(
+    img: &ImageAnalysisStatus,
+    occurrence_id: String,
+    info: &SessionInfo,
+) -> Option<Vec<String>> {
+    let label = img.label()?;
+    let name = scientific_name(label)?;
+    let (verification, remarks) = match (&img.reviewed_label, &img.outcome) {
+        (Some(_), _) => (
+            "verified",
+            "Identified from photo by a human reviewer".to_string(),
+        ),
+        (None, ImageAnalysisOutcome::Analyzed(res)) => (
+            "unverified",
+            format!(
+                "Identified from photo by image classifier, confidence {:.3}",
+                res.top_class().map_or(0.0, |(_, score)| score)
+            ),
+        ),
+        (None, _) => return None,
+    };
+    let exif = &img.data.exif;
+    let (latitude, longitude) = match (exif.latitude, exif.longitude) {
+        (Some(lat), Some(lon)) => (Some(lat), Some(lon)),
+        _ => (info.latitude, info.longitude),
+    };
+
+    Some(vec![
+        occurrence_id,
+        "MachineObservation".to_string(),
+        img.count.to_string(),
+        name.to_string(),
+        clean(label),
+        "Animalia".to_string(),
+        "Cygnus".to_string(),
+        exif.date_time_original
+            .clone()
+            .unwrap_or_else(|| info.date.clone()),
+        clean(&info.observer),
+        clean(&info.site),
+        latitude.map(|v| v.to_string()).unwrap_or_default(),
+        longitude.map(|v| v.to_string()).unwrap_or_default(),
+        if latitude.is_some() { "WGS84" } else { "" }.to_string(),
+        clean(&img.data.name),
+        String::new(),
+        verification.to_string(),
+        remarks,
+    ])
+}
+
+fn meta_xml() -> String {
+    let fields: String = FIELDS
+        .iter()
+        .enumerate()
+        .map(|(i, f)| format!("    <field index=\"{i}\" term=\"{DWC}{f}\"/>\n"))
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<archive xmlns="http://rs.tdwg.org/dwc/text/" metadata="eml.xml">
+  <core encoding="UTF-8" fieldsTerminatedBy="\t" linesTerminatedBy="\n" fieldsEnclosedBy="" ignoreHeaderLines="1" rowType="{DWC}Occurrence">
+    <files>
+      <location>occurrence.txt</location>
+    </files>
+    <id index="0"/>
+{fields}  </core>
+</archive>
+"#
+    )
+}
+
+fn eml_xml(record_count: usize, info: &SessionInfo) -> String {
+    let now = iso_timestamp(js_sys::Date::now());
+    let date = &now[..10];
+    let title = if info.site.is_empty() {
+        format!("Swan photo survey, exported {date}")
+    } else {
+        format!("Swan photo survey at {}, exported {date}", info.site)
+    };
+    let title = xml_escape(&title);
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<eml:eml xmlns:eml="eml://ecoinformatics.org/eml-2.1.1" packageId="swan-survey-{now}" system="image-classifier-frontend" scope="system" xml:lang="en">
+  <dataset>
+    <title>{title}</title>
+    <creator><organizationName>image-classifier-frontend</organizationName></creator>
+    <pubDate>{date}</pubDate>
+    <language>en</language>
+    <abstract><para>{record_count} swan occurrences identified from photographs.</para></abstract>
+  </dataset>
+</eml:eml>
+"#
+    )
+}
+
+/// Build the archive from every image whose label maps to a known species.
+/// Returns the archive and the number of images left out.
+pub fn session_dwca(session: &ExportSession) -> (Blob, usize) {
+    let images = session.images;
+    let mut occurrences = FIELDS.join("\t") + "\n";
+    let mut count = 0;
+    let mut seen = HashSet::new();
+    for img in images {
+        // Copies of a file kept side by side share a hash, but each row needs its own ID.
+        let occurrence_id = if seen.insert(img.data.sha256.as_str()) {
+            format!("urn:sha256:{}", img.data.sha256)
+        } else {
+            format!("urn:sha256:{}:{}", img.data.sha256, img.id)
+        };
+        if let Some(row) = occurrence_row(img, occurrence_id, session.info) {
+            occurrences.push_str(&row.join("\t"));
+            occurrences.push('\n');
+            count += 1;
+        }
+    }
+
+    let mut zip = ZipWriter::new();
+    zip.add_file("occurrence.txt", occurrences.as_bytes());
+    zip.add_file("meta.xml", meta_xml().as_bytes());
+    zip.add_file("eml.xml", eml_xml(count, session.info).as_bytes());
+    (zip.finish(), images.len() - count)
+}
+
+pub struct DarwinCoreExporter;
+
+impl Exporter for DarwinCoreExporter {
+    fn name(&self) -> &'static str {
+        "Darwin Core Archive"
+    }
+
+    fn file_stem(&self) -> &'static str {
+        "occurrences"
+    }
+
+    fn file_extension(&self) -> &'static str {
+        "zip"
+    }
+
+    fn mime_type(&self) -> &'static str {
+        "application/zip"
+    }
+
+    fn write(&self, session: &ExportSession) -> ExportOutput {
+        let (archive, skipped) = session_dwca(session);
+        let output = ExportOutput::new(archive);
+        if skipped > 0 {
+            output.with_summary(format!("{skipped} images were left out of the Darwin Core Archive because they have no known species label."))
+        } else {
+            output
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+
+use gloo::file::Blob;
+
+use crate::metadata::ExifRecord;
+use crate::session::{species_counts, SessionInfo};
+use crate::views::image_analysis::{ImageAnalysisOutcome, ImageId};
+
+use super::{ExportOutput, ExportSession, Exporter};
+
+/// Layout of the JSON written by [`session_json`], for readers to check. Version 1 had
+/// no version field and mapped file names straight to analysis outcomes; version 2 was
+/// [`SessionRecord`] with the images keyed by file name, which lost all but one of the
+/// images sharing a name; version 3 lists them. Adding fields keeps the version, and
+/// anything else bumps it.
+pub const JSON_VERSION: u32 = 3;
+
+#[derive(serde::Serialize)]
+struct ImageRecord<'a> {
+    name: &'a str,
+    outcome: &'a ImageAnalysisOutcome,
+    sha256: &'a str,
+    /// Shared by near-duplicate images, such as the frames of a burst.
+    burst_group: Option<ImageId>,
+    /// Individuals in the photo.
+    count: u32,
+    exif: ExifRecord<'a>,
+}
+
+#[derive(serde::Serialize)]
+struct SessionRecord<'a> {
+    version: u32,
+    session: &'a SessionInfo,
+    species_counts: BTreeMap<String, u32>,
+    images: Vec<ImageRecord<'a>>,
+}
+
+/// The session details and counts, and the name, outcome, content hash and photo
+/// metadata of every image, in session order.
+pub fn session_json(session: &ExportSession) -> String {
+    let records = session
+        .images
+        .iter()
+        .map(|img| ImageRecord {
+            name: &img.data.name,
+            outcome: &img.outcome,
+            sha256: &img.data.sha256,
+            burst_group: img.burst.map(|group| group.id),
+            count: img.count,
+            exif: img.data.exif.record(),
+        })
+        .collect();
+    serde_json::to_string(&SessionRecord {
+        version: JSON_VERSION,
+        session: session.info,
+        species_counts: species_counts(session.images),
+        images: records,
+    })
+    .unwrap()
+}
+
+pub struct JsonExporter;
+
+impl Exporter for JsonExporter {
+    fn name(&self) -> &'static str {
+        "All results as JSON"
+    }
+
+    fn file_extension(&self) -> &'static str {
+        "json"
+    }
+
+    fn mime_type(&self) -> &'static str {
+        "application/json"
+    }
+
+    fn write(&self, session: &ExportSession) -> ExportOutput {
+        let json = session_json(session);
+        ExportOutput::new(Blob::new_with_options(
+            json.as_str(),
+            Some(self.mime_type()),
+        ))
+    }
+}
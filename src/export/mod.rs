@@ -0,0 +1,88 @@
+//! Turning an analysis session into files for download.
+//!
+//! Every format implements [`Exporter`] and is listed in [`all_exporters`];
+//! the analysis view only knows about the trait.
+
+pub mod csv;
+pub mod dataset;
+pub mod dwca;
+pub mod json;
+pub mod sorted_zip;
+
+use std::rc::Rc;
+
+use gloo::file::Blob;
+use wasm_bindgen::JsValue;
+use yew::prelude::*;
+
+use crate::session::SessionInfo;
+use crate::views::image_analysis::ImageAnalysisStatus;
+
+/// Everything an export can draw from.
+pub struct ExportSession<'a> {
+    pub images: &'a [ImageAnalysisStatus],
+    pub info: &'a SessionInfo,
+}
+
+/// A finished export.
+pub struct ExportOutput {
+    pub data: Blob,
+    /// Something worth telling the user about the export, e.g. how many images were left out.
+    pub summary: Option<String>,
+}
+
+impl ExportOutput {
+    pub fn new(data: Blob) -> Self {
+        Self {
+            data,
+            summary: None,
+        }
+    }
+
+    pub fn with_summary(mut self, summary: String) -> Self {
+        self.summary = Some(summary);
+        self
+    }
+}
+
+pub trait Exporter {
+    /// Shown in the list of export formats.
+    fn name(&self) -> &'static str;
+
+    /// Name of the downloaded file, without the extension.
+    fn file_stem(&self) -> &'static str {
+        "labels"
+    }
+
+    fn file_extension(&self) -> &'static str;
+
+    fn mime_type(&self) -> &'static str;
+
+    fn write(&self, session: &ExportSession) -> ExportOutput;
+
+    /// A form for the format's settings. When they change, `on_change` gets
+    /// a reconfigured exporter to use in place of this one.
+    fn view_options(&self, _on_change: Callback<Rc<dyn Exporter>>) -> Html {
+        html!()
+    }
+
+    fn file_name(&self) -> String {
+        format!("{}.{}", self.file_stem(), self.file_extension())
+    }
+}
+
+/// Every available export format, with default settings.
+pub fn all_exporters() -> Vec<Rc<dyn Exporter>> {
+    vec![
+        Rc::new(json::JsonExporter),
+        Rc::new(csv::CsvExporter::default()),
+        Rc::new(dwca::DarwinCoreExporter),
+        Rc::new(sorted_zip::SortedZipExporter),
+        Rc::new(dataset::DatasetExporter::default()),
+    ]
+}
+
+/// Format a `Date.now()`-style timestamp as ISO 8601 in UTC.
+pub fn iso_timestamp(ms: f64) -> String {
+    String::from(js_sys::Date::new(&JsValue::from_f64(ms)).to_iso_string())
+}
@@ -0,0 +1,58 @@
+//! A ZIP with every image in a folder named after its class, for building training sets.
+
+use gloo::file::Blob;
+
+use crate::views::image_analysis::ImageAnalysisStatus;
+use crate::zip::{sanitize_path_component, ZipWriter};
+
+use super::csv::{session_csv, CsvOptions};
+use super::json::session_json;
+use super::{ExportOutput, ExportSession, Exporter};
+
+/// Folder for images that have neither a prediction nor a reviewed label.
+const UNLABELED_FOLDER: &str = "unknown";
+
+/// Images go under `<class>/<file name>`, using the reviewed label when there is one;
+/// the manifests are added at the top level. `labels.json` is the same versioned
+/// document as the JSON export.
+pub fn session_sorted_zip(
+    images: &[ImageAnalysisStatus],
+    json_manifest: &str,
+    csv_manifest: &str,
+) -> Blob {
+    let mut zip = ZipWriter::new();
+    for img in images {
+        let folder = sanitize_path_component(img.label().unwrap_or(UNLABELED_FOLDER));
+        let name = sanitize_path_component(&img.data.name);
+        zip.add_blob(&format!("{folder}/{name}"), &img.data.blob, img.data.crc32);
+    }
+    zip.add_file("labels.json", json_manifest.as_bytes());
+    zip.add_file("labels.csv", csv_manifest.as_bytes());
+    zip.finish()
+}
+
+pub struct SortedZipExporter;
+
+impl Exporter for SortedZipExporter {
+    fn name(&self) -> &'static str {
+        "Images sorted into class folders (ZIP)"
+    }
+
+    fn file_stem(&self) -> &'static str {
+        "images_by_class"
+    }
+
+    fn file_extension(&self) -> &'static str {
+        "zip"
+    }
+
+    fn mime_type(&self) -> &'static str {
+        "application/zip"
+    }
+
+    fn write(&self, session: &ExportSession) -> ExportOutput {
+        let json = session_json(session);
+        let csv = session_csv(session, &CsvOptions::all_columns());
+        ExportOutput::new(session_sorted_zip(session.images, &json, &csv))
+    }
+}
@@ -0,0 +1,329 @@
+//! Decoding, resizing and re-encoding images with the browser's canvas.
+
+use std::ops::Deref;
+
+use gloo::file::{Blob, ObjectUrl};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    CanvasRenderingContext2d, HtmlCanvasElement, ImageBitmap, ImageBitmapOptions, ImageOrientation,
+    ResizeQuality,
+};
+
+/// Longest edge of the previews shown in the analysis rows.
+pub const THUMBNAIL_EDGE: u32 = 320;
+
+/// An object URL for an in-memory image; revoked when the last clone is dropped.
+#[derive(Clone)]
+pub struct BlobUrl(ObjectUrl);
+
+impl BlobUrl {
+    pub fn new(blob: Blob) -> Self {
+        Self(ObjectUrl::from(blob))
+    }
+}
+
+impl Deref for BlobUrl {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for BlobUrl {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl std::fmt::Debug for BlobUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("BlobUrl").field(&&**self).finish()
+    }
+}
+
+pub(crate) fn js_error(what: &str, err: JsValue) -> String {
+    format!(
+        "{what}: {}",
+        err.as_string().unwrap_or_else(|| format!("{err:?}"))
+    )
+}
+
+/// Decode an encoded image (JPEG, PNG, ...) into an upright bitmap, applying its
+/// EXIF orientation.
+pub async fn decode(blob: &Blob) -> Result<ImageBitmap, String> {
+    let mut options = ImageBitmapOptions::new();
+    options.image_orientation(ImageOrientation::FromImage);
+    let promise = gloo::utils::window()
+        .create_image_bitmap_with_blob_and_image_bitmap_options(blob.as_ref(), &options)
+        .map_err(|e| js_error("Failed to start decoding image", e))?;
+    let bitmap = JsFuture::from(promise)
+        .await
+        .map_err(|e| js_error("Failed to decode image", e))?;
+    Ok(bitmap.unchecked_into())
+}
+
+/// Shrink a drawn image to a small size, letting the browser filter it properly.
+async fn resize(
+    canvas: &HtmlCanvasElement,
+    width: u32,
+    height: u32,
+) -> Result<ImageBitmap, String> {
+    let mut options = ImageBitmapOptions::new();
+    options
+        .resize_width(width)
+        .resize_height(height)
+        .resize_quality(ResizeQuality::High);
+    let promise = gloo::utils::window()
+        .create_image_bitmap_with_html_canvas_element_and_image_bitmap_options(canvas, &options)
+        .map_err(|e| js_error("Failed to start resizing image", e))?;
+    let bitmap = JsFuture::from(promise)
+        .await
+        .map_err(|e| js_error("Failed to resize image", e))?;
+    Ok(bitmap.unchecked_into())
+}
+
+/// A canvas of the given size, with its 2D context.
+pub fn canvas(
+    width: u32,
+    height: u32,
+) -> Result<(HtmlCanvasElement, CanvasRenderingContext2d), String> {
+    let canvas: HtmlCanvasElement = gloo::utils::document()
+        .create_element("canvas")
+        .map_err(|e| js_error("Failed to create canvas", e))?
+        .unchecked_into();
+    canvas.set_width(width);
+    canvas.set_height(height);
+    let context = canvas
+        .get_context("2d")
+        .map_err(|e| js_error("Failed to get canvas context", e))?
+        .ok_or("Canvas has no 2D context")?
+        .unchecked_into();
+    Ok((canvas, context))
+}
+
+/// Encode the canvas contents; `quality` (0 to 1) applies to lossy formats.
+pub async fn encode(
+    canvas: &HtmlCanvasElement,
+    mime_type: &str,
+    quality: f64,
+) -> Result<Blob, String> {
+    let mut result = Ok(());
+    let promise = js_sys::Promise::new(&mut |resolve, reject| {
+        let callback = Closure::once_into_js(move |blob: JsValue| {
+            if blob.is_null() {
+                let _ = reject.call1(
+                    &JsValue::NULL,
+                    &JsValue::from_str("the browser could not encode the image"),
+                );
+            } else {
+                let _ = resolve.call1(&JsValue::NULL, &blob);
+            }
+        });
+        result = canvas.to_blob_with_type_and_encoder_options(
+            callback.unchecked_ref(),
+            mime_type,
+            &JsValue::from_f64(quality),
+        );
+    });
+    result.map_err(|e| js_error("Failed to start encoding image", e))?;
+    let blob = JsFuture::from(promise)
+        .await
+        .map_err(|e| js_error("Failed to encode image", e))?;
+    Ok(Blob::from(blob.unchecked_into::<web_sys::Blob>()))
+}
+
+/// The size that fits `width` x `height` into a `max_edge` square, never enlarging.
+pub fn fit(width: u32, height: u32, max_edge: u32) -> (u32, u32) {
+    let longest = width.max(height);
+    if longest <= max_edge || longest == 0 {
+        return (width, height);
+    }
+    let scale = max_edge as f64 / longest as f64;
+    (
+        ((width as f64 * scale).round() as u32).max(1),
+        ((height as f64 * scale).round() as u32).max(1),
+    )
+}
+
+/// Formats an image can be re-encoded to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Jpeg,
+    Webp,
+    /// Lossless, and the one format every browser can encode.
+    Png,
+}
+
+impl OutputFormat {
+    /// The lossy formats, which are the ones worth offering for shrinking images.
+    pub const ALL: [OutputFormat; 2] = [OutputFormat::Jpeg, OutputFormat::Webp];
+
+    pub fn from_mime_type(mime_type: &str) -> Option<Self> {
+        match mime_type {
+            "image/jpeg" => Some(OutputFormat::Jpeg),
+            "image/webp" => Some(OutputFormat::Webp),
+            "image/png" => Some(OutputFormat::Png),
+            _ => None,
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Webp => "image/webp",
+            OutputFormat::Png => "image/png",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "JPEG",
+            OutputFormat::Webp => "WebP",
+            OutputFormat::Png => "PNG",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Webp => "webp",
+            OutputFormat::Png => "png",
+        }
+    }
+}
+
+/// How images are shrunk before they are sent for analysis.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DownscaleOptions {
+    pub enabled: bool,
+    /// Longest edge of the result, in pixels.
+    pub max_edge: u32,
+    pub format: OutputFormat,
+    /// Encoder quality, from 0 to 1.
+    pub quality: f64,
+}
+
+impl Default for DownscaleOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_edge: 1024,
+            format: OutputFormat::Jpeg,
+            quality: 0.85,
+        }
+    }
+}
+
+/// A rotation and mirroring applied on top of the image's own orientation.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Transform {
+    /// Clockwise quarter turns, 0 to 3.
+    pub quarter_turns: u8,
+    /// Mirrored left to right before rotating.
+    pub mirrored: bool,
+}
+
+impl Transform {
+    pub fn is_identity(self) -> bool {
+        self == Self::default()
+    }
+
+    /// This transform followed by a quarter turn clockwise.
+    pub fn rotated_clockwise(self) -> Self {
+        Self {
+            quarter_turns: (self.quarter_turns + 1) % 4,
+            ..self
+        }
+    }
+
+    /// This transform followed by mirroring the result left to right.
+    pub fn flipped(self) -> Self {
+        Self {
+            quarter_turns: (4 - self.quarter_turns) % 4,
+            mirrored: !self.mirrored,
+        }
+    }
+}
+
+/// Shrink the image to fit in a `max_edge` square, apply `transform`, and re-encode it.
+pub async fn downscale(
+    image: &Blob,
+    max_edge: u32,
+    transform: Transform,
+    format: OutputFormat,
+    quality: f64,
+) -> Result<Blob, String> {
+    let canvas = draw(image, max_edge, transform).await?;
+    encode(&canvas, format.mime_type(), quality).await
+}
+
+/// Draw the image on a canvas, shrunk to fit in a `max_edge` square and transformed.
+async fn draw(
+    image: &Blob,
+    max_edge: u32,
+    transform: Transform,
+) -> Result<HtmlCanvasElement, String> {
+    let bitmap = decode(image).await?;
+    let (width, height) = fit(bitmap.width(), bitmap.height(), max_edge);
+    let (canvas_width, canvas_height) = if transform.quarter_turns % 2 == 1 {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    let (canvas, context) = canvas(canvas_width, canvas_height)?;
+    // Draw centered on the origin, so rotating and mirroring keep the image on the canvas.
+    context
+        .translate(canvas_width as f64 / 2.0, canvas_height as f64 / 2.0)
+        .and_then(|_| context.rotate(transform.quarter_turns as f64 * std::f64::consts::FRAC_PI_2))
+        .and_then(|_| context.scale(if transform.mirrored { -1.0 } else { 1.0 }, 1.0))
+        .and_then(|_| {
+            context.draw_image_with_image_bitmap_and_dw_and_dh(
+                &bitmap,
+                -(width as f64) / 2.0,
+                -(height as f64) / 2.0,
+                width as f64,
+                height as f64,
+            )
+        })
+        .map_err(|e| js_error("Failed to draw image", e))?;
+    bitmap.close();
+    Ok(canvas)
+}
+
+/// A small, upright JPEG preview of the image, and the [`dhash`] of that preview, so
+/// that the image is decoded once for both.
+pub async fn thumbnail(image: &Blob, transform: Transform) -> Result<(BlobUrl, u64), String> {
+    let canvas = draw(image, THUMBNAIL_EDGE, transform).await?;
+    let hash = dhash(&canvas).await?;
+    let preview = encode(&canvas, OutputFormat::Jpeg.mime_type(), 0.8).await?;
+    Ok((BlobUrl::new(preview), hash))
+}
+
+/// Difference hash of the drawn image: one bit per pair of neighbouring pixels in a
+/// 9x8 grayscale version, set where the right one is brighter. Similar pictures differ
+/// in few bits.
+pub async fn dhash(image: &HtmlCanvasElement) -> Result<u64, String> {
+    let bitmap = resize(image, 9, 8).await?;
+    let (_canvas, context) = canvas(9, 8)?;
+    context
+        .draw_image_with_image_bitmap(&bitmap, 0.0, 0.0)
+        .map_err(|e| js_error("Failed to draw image", e))?;
+    bitmap.close();
+    let pixels = context
+        .get_image_data(0.0, 0.0, 9.0, 8.0)
+        .map_err(|e| js_error("Failed to read pixels", e))?
+        .data();
+    let luma = |x: usize, y: usize| {
+        let i = (y * 9 + x) * 4;
+        0.299 * pixels[i] as f64 + 0.587 * pixels[i + 1] as f64 + 0.114 * pixels[i + 2] as f64
+    };
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash = (hash << 1) | (luma(x + 1, y) > luma(x, y)) as u64;
+        }
+    }
+    Ok(hash)
+}
@@ -5,13 +5,23 @@ use yew::prelude::*;
 use yew_router::prelude::*;
 
 use crate::components::layout::main_container::Main;
+use crate::components::lazy_mount::LazyMountObserver;
 use crate::components::nav::Nav;
-use crate::views::image_analysis::ImageAnalysisView;
+use crate::views::image_analysis::{AnalysisQueue, ImageAnalysisView};
 use crate::views::not_found::NotFound;
 
+mod api;
 mod components;
+mod csv;
+mod download;
+mod export;
+mod imaging;
+mod metadata;
+mod privacy;
+mod session;
+mod upload;
 mod views;
-
+mod zip;
 
 macro_rules! root_url {
     () => {
@@ -46,12 +56,18 @@ fn switch(routes: Route) -> Html {
 
 #[function_component]
 fn App() -> Html {
+    let analysis_queue = use_state(AnalysisQueue::default);
+    let lazy_mount = use_state(LazyMountObserver::new);
     html! {
         <BrowserRouter>
-            <Nav />
-            <Main>
-                    <Switch<Route> render={switch} />
-            </Main>
+            <ContextProvider<AnalysisQueue> context={(*analysis_queue).clone()}>
+            <ContextProvider<LazyMountObserver> context={(*lazy_mount).clone()}>
+                <Nav />
+                <Main>
+                        <Switch<Route> render={switch} />
+                </Main>
+            </ContextProvider<LazyMountObserver>>
+            </ContextProvider<AnalysisQueue>>
         </BrowserRouter>
     }
 }
@@ -0,0 +1,196 @@
+//! Reading the EXIF metadata that cameras and phones embed in photos.
+
+use exif::{In, Reader, Tag, Value};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExifData {
+    /// When the photo was taken, as `YYYY-MM-DDTHH:MM:SS` in the camera's local time.
+    pub date_time_original: Option<String>,
+    /// Decimal degrees, positive north.
+    pub latitude: Option<f64>,
+    /// Decimal degrees, positive east.
+    pub longitude: Option<f64>,
+    /// Meters above sea level.
+    pub altitude: Option<f64>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens_model: Option<String>,
+    /// The EXIF orientation code, 1 (upright) to 8.
+    pub orientation: Option<u32>,
+    /// Seconds.
+    pub exposure_time: Option<f64>,
+    pub f_number: Option<f64>,
+    pub iso: Option<u32>,
+    /// Millimeters.
+    pub focal_length: Option<f64>,
+    /// Every tag of the primary image, for deciding what to strip before upload.
+    pub tags: Vec<Tag>,
+}
+
+/// The metadata that goes into exports and archive uploads: when and where the photo
+/// was taken, and with what camera.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct ExifRecord<'a> {
+    pub date_time_original: Option<&'a str>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub altitude: Option<f64>,
+    pub camera_make: Option<&'a str>,
+    pub camera_model: Option<&'a str>,
+}
+
+impl ExifData {
+    pub fn record(&self) -> ExifRecord<'_> {
+        ExifRecord {
+            date_time_original: self.date_time_original.as_deref(),
+            latitude: self.latitude,
+            longitude: self.longitude,
+            altitude: self.altitude,
+            camera_make: self.camera_make.as_deref(),
+            camera_model: self.camera_model.as_deref(),
+        }
+    }
+
+    /// Make and model together, without repeating the make when the model already has it.
+    pub fn camera(&self) -> Option<String> {
+        match (&self.camera_make, &self.camera_model) {
+            (Some(make), Some(model)) if model.starts_with(make.as_str()) => Some(model.clone()),
+            (Some(make), Some(model)) => Some(format!("{make} {model}")),
+            (make, model) => make.clone().or_else(|| model.clone()),
+        }
+    }
+
+    /// Human-readable fields that are present, for display.
+    pub fn summary(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![];
+        if let Some(date) = &self.date_time_original {
+            fields.push(("Taken", date.replace('T', " ")));
+        }
+        if let (Some(lat), Some(lon)) = (self.latitude, self.longitude) {
+            fields.push(("Location", format!("{lat:.5}, {lon:.5}")));
+        }
+        if let Some(altitude) = self.altitude {
+            fields.push(("Altitude", format!("{altitude:.0} m")));
+        }
+        if let Some(camera) = self.camera() {
+            fields.push(("Camera", camera));
+        }
+        if let Some(lens) = &self.lens_model {
+            fields.push(("Lens", lens.clone()));
+        }
+        if let Some(orientation) = self.orientation {
+            fields.push(("Orientation", orientation_name(orientation).to_string()));
+        }
+        if let Some(t) = self.exposure_time {
+            let exposure = if t > 0.0 && t < 1.0 {
+                format!("1/{:.0} s", 1.0 / t)
+            } else {
+                format!("{t} s")
+            };
+            fields.push(("Exposure", exposure));
+        }
+        if let Some(f) = self.f_number {
+            fields.push(("Aperture", format!("f/{f:.1}")));
+        }
+        if let Some(iso) = self.iso {
+            fields.push(("ISO", iso.to_string()));
+        }
+        if let Some(mm) = self.focal_length {
+            fields.push(("Focal length", format!("{mm:.0} mm")));
+        }
+        fields
+    }
+}
+
+/// What an EXIF orientation code means for the stored pixels.
+pub fn orientation_name(orientation: u32) -> &'static str {
+    match orientation {
+        1 => "Upright",
+        2 => "Mirrored",
+        3 => "Rotated 180°",
+        4 => "Flipped vertically",
+        5 => "Mirrored, rotated 90° counter-clockwise",
+        6 => "Rotated 90° clockwise",
+        7 => "Mirrored, rotated 90° clockwise",
+        8 => "Rotated 90° counter-clockwise",
+        _ => "Unknown",
+    }
+}
+
+/// Extract the metadata we care about; anything missing or malformed is left empty.
+pub fn read_exif(data: &[u8]) -> ExifData {
+    let Ok(exif) = Reader::new().read_from_container(&mut std::io::Cursor::new(data)) else {
+        return ExifData::default();
+    };
+
+    let ascii = |tag: Tag| match exif.get_field(tag, In::PRIMARY).map(|f| &f.value) {
+        Some(Value::Ascii(v)) => v.first().cloned(),
+        _ => None,
+    };
+
+    let text = |tag: Tag| {
+        ascii(tag)
+            .map(|v| String::from_utf8_lossy(&v).trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+    let rational = |tag: Tag| match exif.get_field(tag, In::PRIMARY).map(|f| &f.value) {
+        Some(Value::Rational(v)) => v.first().map(|r| r.to_f64()).filter(|v| v.is_finite()),
+        _ => None,
+    };
+    let uint = |tag: Tag| {
+        exif.get_field(tag, In::PRIMARY)
+            .and_then(|f| f.value.get_uint(0))
+    };
+
+    let date_time_original = ascii(Tag::DateTimeOriginal)
+        .or_else(|| ascii(Tag::DateTime))
+        .and_then(|v| exif::DateTime::from_ascii(&v).ok())
+        .map(|dt| {
+            format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+                dt.year, dt.month, dt.day, dt.hour, dt.minute, dt.second
+            )
+        });
+
+    let coordinate = |tag: Tag, ref_tag: Tag, negative: u8| {
+        let degrees = match exif.get_field(tag, In::PRIMARY).map(|f| &f.value) {
+            Some(Value::Rational(v)) if v.len() >= 3 => {
+                v[0].to_f64() + v[1].to_f64() / 60.0 + v[2].to_f64() / 3600.0
+            }
+            _ => return None,
+        };
+        if !degrees.is_finite() {
+            return None;
+        }
+        match ascii(ref_tag).and_then(|r| r.first().copied()) {
+            Some(r) if r == negative => Some(-degrees),
+            _ => Some(degrees),
+        }
+    };
+
+    // A reference of 1 means below sea level.
+    let altitude = rational(Tag::GPSAltitude).map(|a| match uint(Tag::GPSAltitudeRef) {
+        Some(1) => -a,
+        _ => a,
+    });
+
+    ExifData {
+        date_time_original,
+        latitude: coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S'),
+        longitude: coordinate(Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W'),
+        altitude,
+        camera_make: text(Tag::Make),
+        camera_model: text(Tag::Model),
+        lens_model: text(Tag::LensModel),
+        orientation: uint(Tag::Orientation).filter(|o| (1..=8).contains(o)),
+        exposure_time: rational(Tag::ExposureTime),
+        f_number: rational(Tag::FNumber),
+        iso: uint(Tag::PhotographicSensitivity),
+        focal_length: rational(Tag::FocalLength),
+        tags: exif
+            .fields()
+            .filter(|f| f.ifd_num == In::PRIMARY)
+            .map(|f| f.tag)
+            .collect(),
+    }
+}
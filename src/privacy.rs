@@ -0,0 +1,512 @@
+//! Removing location and personal details from photos before they are archived.
+
+use std::collections::BTreeMap;
+use std::io::Cursor;
+
+use exif::experimental::Writer;
+use exif::{Context, Field, In, Rational, Reader, Tag, Value};
+
+use crate::metadata::ExifData;
+
+/// Precision of coarsened coordinates, in degrees (about 11 km north to south).
+const COARSE_DEGREES: f64 = 0.1;
+
+/// Tags that identify the photographer or the camera itself.
+const PERSONAL_TAGS: [Tag; 9] = [
+    Tag::Artist,
+    Tag::Copyright,
+    Tag::CameraOwnerName,
+    Tag::BodySerialNumber,
+    Tag::LensSerialNumber,
+    Tag::ImageUniqueID,
+    Tag::ImageDescription,
+    Tag::UserComment,
+    // Vendor-specific, and often holds the serial number.
+    Tag::MakerNote,
+];
+
+/// GPS tags kept, with rounded values, when coarsening the location.
+const COARSE_GPS_TAGS: [Tag; 5] = [
+    Tag::GPSVersionID,
+    Tag::GPSLatitudeRef,
+    Tag::GPSLatitude,
+    Tag::GPSLongitudeRef,
+    Tag::GPSLongitude,
+];
+
+const XMP_SIGNATURE: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+/// XMP properties that hold a place, from the EXIF and IPTC Core schemas.
+const XMP_LOCATION_PROPERTIES: [&[u8]; 3] = [b"GPSLatitude", b"GPSLongitude", b"LocationCreated"];
+const EXIF_SIGNATURE: &[u8] = b"Exif\0\0";
+const APP1: u8 = 0xE1;
+/// Photoshop resources, which carry IPTC captions, bylines and places.
+const APP13: u8 = 0xED;
+const SOS: u8 = 0xDA;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LocationPolicy {
+    Keep,
+    Coarsen,
+    Remove,
+}
+
+impl LocationPolicy {
+    pub const ALL: [LocationPolicy; 3] = [
+        LocationPolicy::Keep,
+        LocationPolicy::Coarsen,
+        LocationPolicy::Remove,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LocationPolicy::Keep => "Keep exact location",
+            LocationPolicy::Coarsen => "Round location to about 10 km",
+            LocationPolicy::Remove => "Remove location",
+        }
+    }
+}
+
+/// What to take out of photos before they are uploaded to the archive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrivacyOptions {
+    pub location: LocationPolicy,
+    /// Remove names, serial numbers, comments, XMP and IPTC.
+    pub strip_personal: bool,
+}
+
+impl Default for PrivacyOptions {
+    fn default() -> Self {
+        Self {
+            location: LocationPolicy::Coarsen,
+            strip_personal: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Action {
+    Keep,
+    Coarsen,
+    Remove,
+}
+
+fn action(tag: Tag, options: &PrivacyOptions) -> Action {
+    if tag.context() == Context::Gps {
+        return match options.location {
+            LocationPolicy::Keep => Action::Keep,
+            LocationPolicy::Coarsen if COARSE_GPS_TAGS.contains(&tag) => {
+                if tag == Tag::GPSLatitude || tag == Tag::GPSLongitude {
+                    Action::Coarsen
+                } else {
+                    Action::Keep
+                }
+            }
+            _ => Action::Remove,
+        };
+    }
+    if options.strip_personal && PERSONAL_TAGS.contains(&tag) {
+        return Action::Remove;
+    }
+    Action::Keep
+}
+
+/// The metadata an upload would remove or coarsen, by description.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MetadataChanges {
+    pub removed: Vec<String>,
+    pub coarsened: Vec<String>,
+}
+
+impl MetadataChanges {
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.coarsened.is_empty()
+    }
+}
+
+fn describe(tag: Tag) -> String {
+    tag.description()
+        .map(str::to_string)
+        .unwrap_or_else(|| tag.to_string())
+}
+
+/// Iterate over the `(marker, payload)` of the JPEG segments before the image data.
+fn jpeg_segments(data: &[u8]) -> impl Iterator<Item = (u8, std::ops::Range<usize>)> + '_ {
+    let mut pos = 2;
+    std::iter::from_fn(move || {
+        // Markers may be preceded by any number of 0xFF fill bytes.
+        while data.get(pos) == Some(&0xFF) && data.get(pos + 1) == Some(&0xFF) {
+            pos += 1;
+        }
+        if data.get(pos) != Some(&0xFF) {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        // The length counts its own two bytes, so anything shorter is corrupt.
+        let len = u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]) as usize;
+        if marker == SOS || len < 2 {
+            return None;
+        }
+        let payload = pos + 4..(pos + 2 + len).min(data.len());
+        pos += 2 + len;
+        Some((marker, payload))
+    })
+}
+
+fn is_jpeg(data: &[u8]) -> bool {
+    data.starts_with(&[0xFF, 0xD8])
+}
+
+fn is_xmp(data: &[u8], marker: u8, payload: &std::ops::Range<usize>) -> bool {
+    marker == APP1 && data[payload.clone()].starts_with(XMP_SIGNATURE)
+}
+
+fn has_location(xmp: &[u8]) -> bool {
+    XMP_LOCATION_PROPERTIES
+        .iter()
+        .any(|property| xmp.windows(property.len()).any(|w| w == *property))
+}
+
+/// Metadata besides EXIF found in a file, noted when it is loaded so that changes can
+/// be planned without its contents.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EmbeddedMetadata {
+    pub is_jpeg: bool,
+    /// The JPEG has XMP or IPTC segments.
+    pub xmp_or_iptc: bool,
+    /// The JPEG's XMP says where the photo was taken.
+    pub xmp_location: bool,
+}
+
+impl EmbeddedMetadata {
+    pub fn scan(data: &[u8]) -> Self {
+        let mut embedded = Self {
+            is_jpeg: is_jpeg(data),
+            ..Self::default()
+        };
+        if !embedded.is_jpeg {
+            return embedded;
+        }
+        for (marker, payload) in jpeg_segments(data) {
+            if marker == APP13 {
+                embedded.xmp_or_iptc = true;
+            } else if is_xmp(data, marker, &payload) {
+                embedded.xmp_or_iptc = true;
+                embedded.xmp_location |= has_location(&data[payload]);
+            }
+        }
+        embedded
+    }
+}
+
+/// Whether the XMP or IPTC segment goes, given the options.
+fn drops_segment(
+    data: &[u8],
+    marker: u8,
+    payload: &std::ops::Range<usize>,
+    options: &PrivacyOptions,
+) -> bool {
+    if marker == APP13 {
+        return options.strip_personal;
+    }
+    is_xmp(data, marker, payload)
+        && (options.strip_personal
+            || (options.location != LocationPolicy::Keep && has_location(&data[payload.clone()])))
+}
+
+/// What [`strip_metadata`] would change in the image.
+pub fn planned_changes(
+    embedded: &EmbeddedMetadata,
+    exif: &ExifData,
+    options: &PrivacyOptions,
+) -> MetadataChanges {
+    let mut changes = MetadataChanges::default();
+    for tag in exif.tags.iter() {
+        match action(*tag, options) {
+            Action::Keep => {}
+            Action::Coarsen => changes.coarsened.push(describe(*tag)),
+            Action::Remove => changes.removed.push(describe(*tag)),
+        }
+    }
+    // XMP is removed rather than coarsened, as it can repeat the place in many forms.
+    if options.strip_personal && embedded.xmp_or_iptc {
+        changes.removed.push("XMP/IPTC metadata".to_string());
+    } else if options.location != LocationPolicy::Keep && embedded.xmp_location {
+        changes
+            .removed
+            .push("XMP metadata with the location".to_string());
+    }
+    changes
+}
+
+/// Tally of planned changes over many images: description to number of images.
+pub fn tally<'a>(changes: impl IntoIterator<Item = &'a [String]>) -> BTreeMap<&'a str, usize> {
+    let mut counts = BTreeMap::new();
+    for fields in changes {
+        for field in fields {
+            *counts.entry(field.as_str()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// Coordinates in decimal degrees as they may be stored with the upload.
+pub fn redact_coordinates(
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    options: &PrivacyOptions,
+) -> (Option<f64>, Option<f64>) {
+    let round = |v: f64| (v / COARSE_DEGREES).round() * COARSE_DEGREES;
+    match options.location {
+        LocationPolicy::Keep => (latitude, longitude),
+        LocationPolicy::Coarsen => (latitude.map(round), longitude.map(round)),
+        LocationPolicy::Remove => (None, None),
+    }
+}
+
+/// The metadata as it may be stored with the upload.
+pub fn redact(exif: &ExifData, options: &PrivacyOptions) -> ExifData {
+    let (latitude, longitude) = redact_coordinates(exif.latitude, exif.longitude, options);
+    let altitude = match options.location {
+        LocationPolicy::Keep => exif.altitude,
+        _ => None,
+    };
+    ExifData {
+        latitude,
+        longitude,
+        altitude,
+        ..exif.clone()
+    }
+}
+
+fn coarsen(value: &Value) -> Value {
+    match value {
+        Value::Rational(v) if v.len() >= 3 => {
+            let degrees = v[0].to_f64() + v[1].to_f64() / 60.0 + v[2].to_f64() / 3600.0;
+            let steps = (degrees / COARSE_DEGREES).round() as u32;
+            let denom = (1.0 / COARSE_DEGREES).round() as u32;
+            Value::Rational(vec![
+                Rational::from((steps, denom)),
+                Rational::from((0, 1)),
+                Rational::from((0, 1)),
+            ])
+        }
+        v => v.clone(),
+    }
+}
+
+/// Whether the image must be re-encoded to drop its metadata, because it is not a JPEG.
+pub fn needs_reencode(
+    embedded: &EmbeddedMetadata,
+    exif: &ExifData,
+    options: &PrivacyOptions,
+) -> bool {
+    !embedded.is_jpeg && !planned_changes(embedded, exif, options).is_empty()
+}
+
+/// Rewrite a JPEG without the metadata `options` asks to remove, leaving the image data
+/// untouched. Returns `None` if there is nothing to change.
+pub fn strip_metadata(
+    data: &[u8],
+    exif: &ExifData,
+    options: &PrivacyOptions,
+) -> Result<Option<Vec<u8>>, String> {
+    let embedded = EmbeddedMetadata::scan(data);
+    if planned_changes(&embedded, exif, options).is_empty() {
+        return Ok(None);
+    }
+    if !embedded.is_jpeg {
+        return Err("only JPEG metadata can be edited in place".to_string());
+    }
+
+    let new_exif = match Reader::new().read_from_container(&mut Cursor::new(data)) {
+        Ok(parsed) => {
+            let fields: Vec<Field> = parsed
+                .fields()
+                .filter(|f| f.ifd_num == In::PRIMARY)
+                .filter_map(|f| match action(f.tag, options) {
+                    Action::Keep => Some(f.clone()),
+                    Action::Coarsen => Some(Field {
+                        value: coarsen(&f.value),
+                        ..f.clone()
+                    }),
+                    Action::Remove => None,
+                })
+                .collect();
+            let mut writer = Writer::new();
+            for field in fields.iter() {
+                writer.push_field(field);
+            }
+            let mut tiff = Cursor::new(Vec::new());
+            writer
+                .write(&mut tiff, parsed.little_endian())
+                .map_err(|e| format!("Failed to write EXIF: {e}"))?;
+            let mut segment = EXIF_SIGNATURE.to_vec();
+            segment.extend(tiff.into_inner());
+            Some(segment)
+        }
+        Err(_) => None,
+    };
+
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&data[..2]);
+    let mut end = 2;
+    for (marker, payload) in jpeg_segments(data) {
+        let segment = payload.start - 4..payload.end;
+        end = segment.end;
+        let is_exif = marker == APP1 && data[payload.clone()].starts_with(EXIF_SIGNATURE);
+        if is_exif {
+            if let Some(new_exif) = &new_exif {
+                let len = u16::try_from(new_exif.len() + 2)
+                    .map_err(|_| "EXIF data too large for a JPEG segment".to_string())?;
+                out.extend_from_slice(&[0xFF, APP1]);
+                out.extend_from_slice(&len.to_be_bytes());
+                out.extend_from_slice(new_exif);
+            }
+            continue;
+        }
+        if drops_segment(data, marker, &payload, options) {
+            continue;
+        }
+        out.extend_from_slice(&data[segment]);
+    }
+    out.extend_from_slice(&data[end..]);
+    Ok(Some(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::read_exif;
+
+    const SCAN_DATA: &[u8] = &[0xFF, SOS, 0x00, 0x04, 0x01, 0x02, 0x03, 0xFF, 0xD9];
+
+    fn segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        let mut out = vec![0xFF, marker];
+        out.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn exif_segment() -> Vec<u8> {
+        let fields = [
+            Field {
+                tag: Tag::Artist,
+                ifd_num: In::PRIMARY,
+                value: Value::Ascii(vec![b"Jane Doe".to_vec()]),
+            },
+            Field {
+                tag: Tag::GPSLatitudeRef,
+                ifd_num: In::PRIMARY,
+                value: Value::Ascii(vec![b"N".to_vec()]),
+            },
+            Field {
+                tag: Tag::GPSLatitude,
+                ifd_num: In::PRIMARY,
+                value: Value::Rational(vec![(59, 1).into(), (56, 1).into(), (24, 1).into()]),
+            },
+        ];
+        let mut writer = Writer::new();
+        for field in fields.iter() {
+            writer.push_field(field);
+        }
+        let mut tiff = Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        let mut payload = EXIF_SIGNATURE.to_vec();
+        payload.extend(tiff.into_inner());
+        segment(APP1, &payload)
+    }
+
+    /// A JPEG with EXIF, XMP with a location and IPTC, then `SCAN_DATA`.
+    fn photo() -> Vec<u8> {
+        let mut xmp = XMP_SIGNATURE.to_vec();
+        xmp.extend_from_slice(b"<rdf:Description exif:GPSLatitude=\"59,56.4N\"/>");
+        let mut data = vec![0xFF, 0xD8];
+        data.extend(exif_segment());
+        data.extend(segment(APP1, &xmp));
+        data.extend(segment(APP13, b"Photoshop 3.0\0"));
+        data.extend_from_slice(SCAN_DATA);
+        data
+    }
+
+    #[test]
+    fn segments_are_listed_up_to_the_image_data() {
+        let data = photo();
+        let markers: Vec<u8> = jpeg_segments(&data).map(|(marker, _)| marker).collect();
+        assert_eq!(markers, [APP1, APP1, APP13]);
+    }
+
+    #[test]
+    fn corrupt_segment_lengths_end_the_listing() {
+        for data in [
+            &[0xFF, 0xD8, 0xFF, APP1, 0x00, 0x00, 0xAA][..],
+            &[0xFF, 0xD8, 0xFF, APP1, 0x00, 0x01, 0xAA],
+            &[0xFF, 0xD8, 0xFF, APP1, 0x00],
+            &[0xFF, 0xD8, 0xFF],
+        ] {
+            assert_eq!(jpeg_segments(data).count(), 0);
+        }
+        // A length running past the end is cut short.
+        let data = [0xFF, 0xD8, 0xFF, APP1, 0x01, 0x00, 0xAA];
+        let segments: Vec<_> = jpeg_segments(&data).collect();
+        assert_eq!(segments, [(APP1, 6..7)]);
+    }
+
+    #[test]
+    fn coarsened_coordinates_are_rounded_to_a_tenth_of_a_degree() {
+        let value = Value::Rational(vec![(59, 1).into(), (56, 1).into(), (24, 1).into()]);
+        let Value::Rational(coarse) = coarsen(&value) else {
+            panic!("not rational");
+        };
+        let degrees: Vec<f64> = coarse.iter().map(|r| r.to_f64()).collect();
+        assert_eq!(degrees, [59.9, 0.0, 0.0]);
+
+        let other = Value::Ascii(vec![b"N".to_vec()]);
+        assert_eq!(format!("{:?}", coarsen(&other)), format!("{other:?}"));
+    }
+
+    #[test]
+    fn stripping_removes_personal_details_and_coarsens_location() {
+        let data = photo();
+        let options = PrivacyOptions::default();
+        let out = strip_metadata(&data, &read_exif(&data), &options)
+            .unwrap()
+            .unwrap();
+
+        let exif = read_exif(&out);
+        assert!(!exif.tags.contains(&Tag::Artist));
+        assert!((exif.latitude.unwrap() - 59.9).abs() < 1e-9);
+        assert!(!EmbeddedMetadata::scan(&out).xmp_or_iptc);
+        assert!(out.ends_with(SCAN_DATA));
+    }
+
+    #[test]
+    fn xmp_with_a_location_goes_with_the_location() {
+        let data = photo();
+        let exif = read_exif(&data);
+        let options = PrivacyOptions {
+            location: LocationPolicy::Remove,
+            strip_personal: false,
+        };
+        let changes = planned_changes(&EmbeddedMetadata::scan(&data), &exif, &options);
+        assert!(changes
+            .removed
+            .contains(&"XMP metadata with the location".to_string()));
+
+        let out = strip_metadata(&data, &exif, &options).unwrap().unwrap();
+        let markers: Vec<u8> = jpeg_segments(&out).map(|(marker, _)| marker).collect();
+        assert_eq!(markers, [APP1, APP13]);
+        assert!(!EmbeddedMetadata::scan(&out).xmp_location);
+        assert_eq!(read_exif(&out).latitude, None);
+        assert!(read_exif(&out).tags.contains(&Tag::Artist));
+    }
+
+    #[test]
+    fn nothing_to_change_leaves_the_file_alone() {
+        let data = photo();
+        let options = PrivacyOptions {
+            location: LocationPolicy::Keep,
+            strip_personal: false,
+        };
+        assert_eq!(strip_metadata(&data, &read_exif(&data), &options), Ok(None));
+    }
+}
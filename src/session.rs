@@ -0,0 +1,32 @@
+//! Details of the outing a set of photos was taken on, shared by every image in the view.
+
+use std::collections::BTreeMap;
+
+use crate::views::image_analysis::ImageAnalysisStatus;
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize)]
+pub struct SessionInfo {
+    pub observer: String,
+    pub site: String,
+    /// Decimal degrees, positive north.
+    pub latitude: Option<f64>,
+    /// Decimal degrees, positive east.
+    pub longitude: Option<f64>,
+    /// The day of the outing, as `YYYY-MM-DD`.
+    pub date: String,
+}
+
+/// Individuals counted per label, over the images that are neither copies of an
+/// earlier file nor collapsed frames of a burst, so that nothing is counted twice.
+pub fn species_counts(images: &[ImageAnalysisStatus]) -> BTreeMap<String, u32> {
+    let mut counts = BTreeMap::new();
+    for img in images {
+        if img.duplicate_of.is_some() || img.collapsed {
+            continue;
+        }
+        if let Some(label) = img.label() {
+            *counts.entry(label.to_string()).or_insert(0) += img.count;
+        }
+    }
+    counts
+}
@@ -0,0 +1,170 @@
+//! Multipart uploads through `XMLHttpRequest`, which, unlike `fetch`, reports how much
+//! of the request body has been sent.
+
+use gloo::file::Blob;
+use js_sys::Promise;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{FormData, ProgressEvent, XmlHttpRequest};
+use yew::Callback;
+
+use crate::imaging::js_error;
+
+/// How far an upload has got.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UploadProgress {
+    /// Bytes of the request body sent so far.
+    pub sent: f64,
+    /// Size of the request body; an estimate until the browser reports it.
+    pub total: f64,
+    /// When the upload started, in milliseconds since the epoch.
+    pub started_at: f64,
+}
+
+impl UploadProgress {
+    pub fn new(estimated_total: f64) -> Self {
+        Self {
+            sent: 0.0,
+            total: estimated_total,
+            started_at: js_sys::Date::now(),
+        }
+    }
+
+    /// Progress from 0 to 1.
+    pub fn fraction(&self) -> f64 {
+        fraction(self.sent, self.total)
+    }
+
+    /// Sent bytes, size, speed and time left, for display.
+    pub fn describe(&self) -> String {
+        describe(self.sent, self.total, self.started_at)
+    }
+}
+
+/// `sent / total` clamped to 0..=1, treating an empty upload as done.
+pub fn fraction(sent: f64, total: f64) -> f64 {
+    if total > 0.0 {
+        (sent / total).clamp(0.0, 1.0)
+    } else {
+        1.0
+    }
+}
+
+/// E.g. "1.2 MB of 3.5 MB, 450 kB/s, 5 s left".
+pub fn describe(sent: f64, total: f64, started_at: f64) -> String {
+    let elapsed = (js_sys::Date::now() - started_at) / 1000.0;
+    let mut text = format!("{} of {}", format_bytes(sent), format_bytes(total));
+    if elapsed > 0.0 && sent > 0.0 {
+        let speed = sent / elapsed;
+        let left = (total - sent).max(0.0) / speed;
+        text += &format!(
+            ", {}/s, {} left",
+            format_bytes(speed),
+            format_duration(left)
+        );
+    }
+    text
+}
+
+pub fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "kB", "MB", "GB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{value:.0} {}", UNITS[unit])
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.ceil() as u64;
+    if seconds < 60 {
+        format!("{seconds} s")
+    } else {
+        format!("{} min {} s", seconds / 60, seconds % 60)
+    }
+}
+
+/// A multipart form body.
+pub struct UploadForm(FormData);
+
+impl UploadForm {
+    pub fn new() -> Result<Self, String> {
+        FormData::new()
+            .map(Self)
+            .map_err(|e| js_error("Failed to create form", e))
+    }
+
+    pub fn text(self, name: &str, value: &str) -> Result<Self, String> {
+        self.0
+            .append_with_str(name, value)
+            .map_err(|e| js_error("Failed to add form field", e))?;
+        Ok(self)
+    }
+
+    pub fn file(self, name: &str, file: &Blob, file_name: &str) -> Result<Self, String> {
+        self.0
+            .append_with_blob_and_filename(name, file.as_ref(), file_name)
+            .map_err(|e| js_error("Failed to add file to form", e))?;
+        Ok(self)
+    }
+}
+
+/// POST the form, reporting `(sent, total)` bytes as the body goes out, and return the
+/// response body.
+pub async fn post(
+    url: &str,
+    form: UploadForm,
+    on_progress: Callback<(f64, f64)>,
+) -> Result<String, String> {
+    let xhr = XmlHttpRequest::new().map_err(|e| js_error("Failed to create request", e))?;
+    xhr.open("POST", url)
+        .map_err(|e| js_error("Failed to open request", e))?;
+
+    let onprogress = Closure::<dyn FnMut(ProgressEvent)>::new(move |ev: ProgressEvent| {
+        if ev.length_computable() {
+            on_progress.emit((ev.loaded(), ev.total()));
+        }
+    });
+    xhr.upload()
+        .map_err(|e| js_error("Failed to watch upload", e))?
+        .set_onprogress(Some(onprogress.as_ref().unchecked_ref()));
+
+    // Kept alive until the request is over, when one of them will have been called.
+    let mut handlers = None;
+    let done = Promise::new(&mut |resolve, reject| {
+        let onload = Closure::<dyn FnMut()>::new(move || {
+            let _ = resolve.call0(&JsValue::NULL);
+        });
+        let onerror = Closure::<dyn FnMut()>::new(move || {
+            let _ = reject.call1(&JsValue::NULL, &JsValue::from_str("network error"));
+        });
+        xhr.set_onload(Some(onload.as_ref().unchecked_ref()));
+        xhr.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+        xhr.set_onabort(Some(onerror.as_ref().unchecked_ref()));
+        handlers = Some((onload, onerror));
+    });
+
+    xhr.send_with_opt_form_data(Some(&form.0))
+        .map_err(|e| js_error("Error sending upload request", e))?;
+    let sent = JsFuture::from(done).await;
+    drop(handlers);
+    drop(onprogress);
+    sent.map_err(|e| js_error("Error sending upload request", e))?;
+
+    let status = xhr.status().unwrap_or(0);
+    if !(200..300).contains(&status) {
+        return Err(format!(
+            "Error status in upload: {status} {}",
+            xhr.status_text().unwrap_or_default()
+        ));
+    }
+    xhr.response_text()
+        .map_err(|e| js_error("Failed to read upload response", e))
+        .map(Option::unwrap_or_default)
+}
@@ -1,23 +1,36 @@
-use std::{collections::HashMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
 
-use base64::Engine;
+use gloo::file::Blob;
 use gloo::timers::callback::Interval;
+use gloo::timers::future::TimeoutFuture;
 use log::{debug, info};
 use reqwest::multipart::Part;
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
 use yew::prelude::*;
 
+use crate::api::{self, SearchResult};
 use crate::components::{
     alert::Alert,
+    downscale_options::DownscaleOptionsForm,
     file_upload_box::{FileDetails, FileUploadBox},
     image_analysis_row::AnalysisReportRow,
+    privacy_options::PrivacyOptionsForm,
+    session_info::SessionInfoForm,
 };
+use crate::download::download_blob;
+use crate::export::{all_exporters, ExportSession, Exporter};
+use crate::imaging::{self, BlobUrl, DownscaleOptions, OutputFormat, Transform};
+use crate::privacy::{self, MetadataChanges, PrivacyOptions};
+use crate::session::{self, SessionInfo};
+use crate::upload::{self, UploadForm, UploadProgress};
 
 use crate::root_url;
 
-//const ANALYSIS_URL: &str = "http://localhost:5000/analyze";
-const ANALYSIS_URL: &str = concat!(root_url!(), "/analyze");
-
-
 const UPLOAD_URL: &str = concat!(root_url!(), "/save");
 
 #[derive(serde::Deserialize, Debug, Clone, PartialEq, serde::Serialize)]
@@ -26,17 +39,191 @@ pub struct ImageAnalysisData {
     pub overall_classification: HashMap<String, f64>,
 }
 
+impl ImageAnalysisData {
+    /// The class with the highest confidence, and that confidence.
+    pub fn top_class(&self) -> Option<(&str, f64)> {
+        self.overall_classification
+            .iter()
+            .max_by(|(_k1, v1), (_k2, v2)| v1.partial_cmp(v2).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(k, v)| (k.as_str(), *v))
+    }
+}
+
+/// What to do with a file whose contents are already in the session.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DuplicatePolicy {
+    Skip,
+    KeepBoth,
+    Replace,
+}
+
+impl DuplicatePolicy {
+    pub const ALL: [DuplicatePolicy; 3] = [
+        DuplicatePolicy::Skip,
+        DuplicatePolicy::KeepBoth,
+        DuplicatePolicy::Replace,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DuplicatePolicy::Skip => "Skip the new copy",
+            DuplicatePolicy::KeepBoth => "Keep both, marked as duplicates",
+            DuplicatePolicy::Replace => "Replace the earlier copy",
+        }
+    }
+}
+
+/// Identifies an image for as long as it is in the session, even if the same file is added twice.
+pub type ImageId = u64;
+
 #[derive(Clone, PartialEq)]
 pub struct ImageAnalysisStatus {
+    pub id: ImageId,
     pub data: Rc<FileDetails>,
     pub outcome: ImageAnalysisOutcome,
+    /// When the image was added to the session, in milliseconds since the epoch.
+    pub added_at: f64,
+    /// When the analysis request for the image completed.
+    pub analyzed_at: Option<f64>,
+    /// The class a human confirmed or corrected, overriding the prediction.
+    pub reviewed_label: Option<String>,
+    /// Downscaled preview for the row; the full image is shown until it is ready.
+    pub thumbnail: Option<BlobUrl>,
+    /// Rotation and mirroring chosen by hand, on top of the EXIF orientation.
+    pub transform: Transform,
+    /// What is sent to the classifier in place of the original file.
+    pub analysis_copy: AnalysisCopy,
+    /// Name of an earlier image in the session with the same contents.
+    pub duplicate_of: Option<String>,
+    /// Perceptual hash, once computed; see [`imaging::dhash`].
+    pub dhash: Option<u64>,
+    /// The near-duplicates (e.g. burst shots) this image belongs to, if any.
+    pub burst: Option<BurstGroup>,
+    /// Shown as a single line, because another frame of its burst was kept.
+    pub collapsed: bool,
+    /// Whether the image has been uploaded to the archive.
+    pub archive: ArchiveStatus,
+    /// What uploading will change in the metadata under the current privacy options.
+    pub metadata_changes: MetadataChanges,
+    /// Tags to archive along with the label, such as location, age class or behaviour.
+    pub tags: Vec<String>,
+    /// Free-text note to archive with the image.
+    pub note: String,
+    /// Number of individuals in the photo.
+    pub count: u32,
 }
 
-type RequestId = usize;
+/// Most uploads to run at once; the rest wait their turn.
+const MAX_CONCURRENT_UPLOADS: usize = 3;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArchiveStatus {
+    NotUploaded,
+    /// Waiting for a free upload slot, see [`MAX_CONCURRENT_UPLOADS`].
+    Queued {
+        force: bool,
+    },
+    /// Checking the archive for the image, then uploading it.
+    Uploading(UploadProgress),
+    /// Uploaded from this session, with the archive's entry if its reply had one.
+    Archived(Option<SearchResult>),
+    /// Not uploaded, because the archive has an image with the same contents.
+    AlreadyArchived(SearchResult),
+    Failed(String),
+}
+
+impl ArchiveStatus {
+    /// Whether an upload may start; an image the archive already has must be forced.
+    pub fn can_upload(&self, force: bool) -> bool {
+        match self {
+            ArchiveStatus::NotUploaded | ArchiveStatus::Failed(_) => true,
+            ArchiveStatus::AlreadyArchived(_) => force,
+            ArchiveStatus::Queued { .. }
+            | ArchiveStatus::Uploading(_)
+            | ArchiveStatus::Archived(_) => false,
+        }
+    }
+
+    /// Whether the tags, note and count have been sent, so that changing them would
+    /// make no difference to the archive.
+    pub fn is_sent(&self) -> bool {
+        matches!(
+            self,
+            ArchiveStatus::Uploading(_) | ArchiveStatus::Archived(_)
+        )
+    }
+}
+
+/// Images whose perceptual hashes are within [`NEAR_DUPLICATE_DISTANCE`] of each other.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BurstGroup {
+    /// The ID of the group's first image.
+    pub id: ImageId,
+    pub size: usize,
+}
+
+/// Most differing bits between the hashes of two near-duplicate images.
+const NEAR_DUPLICATE_DISTANCE: u32 = 10;
 
-pub type AnalysisResponse = HashMap<String, ImageAnalysisData>;
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnalysisCopy {
+    /// Send the original file.
+    Original,
+    /// Being downscaled; the image is not sent until that is done.
+    Pending,
+    /// An upright and possibly smaller re-encoding of the image.
+    Reencoded { file_type: String, blob: Blob },
+}
 
-#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+impl ImageAnalysisStatus {
+    /// The multipart part to send to the classifier, under the original file name
+    /// so that the response can be matched to the image.
+    pub async fn analysis_part(&self) -> Result<Part, String> {
+        match &self.analysis_copy {
+            AnalysisCopy::Reencoded { file_type, blob } => {
+                let data = gloo::file::futures::read_as_bytes(blob)
+                    .await
+                    .map_err(|e| format!("Failed to read re-encoded image: {e}"))?;
+                Ok(Part::bytes(data)
+                    .file_name(self.data.name.clone())
+                    .mime_str(file_type)
+                    .unwrap())
+            }
+            _ => api::file_part(&self.data).await,
+        }
+    }
+
+    /// The reviewed label if there is one, otherwise the predicted class.
+    pub fn label(&self) -> Option<&str> {
+        if let Some(label) = &self.reviewed_label {
+            return Some(label);
+        }
+        match &self.outcome {
+            ImageAnalysisOutcome::Analyzed(res) => res.top_class().map(|(class, _)| class),
+            _ => None,
+        }
+    }
+
+    /// The tags to archive the image with: its label first, then the ones added by hand.
+    /// Without a label, an analyzed image is tagged "unknown" and any other "unknown_tag".
+    pub fn archive_tags(&self) -> Vec<String> {
+        let fallback = match self.outcome {
+            ImageAnalysisOutcome::Analyzed(_) => "unknown",
+            _ => "unknown_tag",
+        };
+        let mut tags = vec![self.label().unwrap_or(fallback).to_string()];
+        for tag in self.tags.iter() {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+        tags
+    }
+}
+
+type RequestId = usize;
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum ImageAnalysisOutcome {
     #[serde(rename = "waiting_to_send")]
     WaitingToSend,
@@ -48,12 +235,67 @@ pub enum ImageAnalysisOutcome {
     Error(String),
 }
 
+/// Images handed over from other pages (e.g. search results sent for re-classification),
+/// picked up by the analysis view the next time it is created.
+#[derive(Clone, Default)]
+pub struct AnalysisQueue(Rc<RefCell<Vec<FileDetails>>>);
+
+impl AnalysisQueue {
+    pub fn push(&self, file: FileDetails) {
+        self.0.borrow_mut().push(file);
+    }
+
+    fn take(&self) -> Vec<FileDetails> {
+        self.0.borrow_mut().drain(..).collect()
+    }
+}
+
+impl PartialEq for AnalysisQueue {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
 pub struct ImageAnalysisView {
     requests_sent: usize,
     images: Vec<ImageAnalysisStatus>,
+    next_image_id: ImageId,
+    // Created once so that rows whose image did not change are not re-rendered.
+    on_delete_row: Callback<ImageId>,
+    on_upload_row: Callback<(ImageId, bool)>,
+    on_review_row: Callback<(ImageId, Option<String>)>,
+    on_transform_row: Callback<(ImageId, Transform)>,
+    on_keep_frame_row: Callback<ImageId>,
+    on_expand_row: Callback<ImageId>,
+    on_tags_row: Callback<(ImageId, Vec<String>)>,
+    on_note_row: Callback<(ImageId, String)>,
+    on_count_row: Callback<(ImageId, u32)>,
     _clock_handle: Interval,
-    uploading: Vec<Rc<FileDetails>>,
     alerts: Vec<Html>,
+    exporters: Vec<Rc<dyn Exporter>>,
+    selected_exporter: usize,
+    /// An export was requested and is being generated.
+    exporting: bool,
+    /// Applied to images as they are added.
+    downscale: DownscaleOptions,
+    /// Applied to images as they are uploaded to the archive.
+    privacy: PrivacyOptions,
+    duplicate_policy: DuplicatePolicy,
+    /// Names of files that were not added because their contents were already in the session.
+    skipped_duplicates: Vec<String>,
+    /// Tags typed into the editor for all images.
+    bulk_tags: String,
+    /// The outing the photos are from, attached to exports and uploads.
+    session: SessionInfo,
+    /// Set while any upload is queued or running.
+    upload_batch: Option<UploadBatch>,
+}
+
+/// Uploads started while others were still queued or running, for the overall progress.
+struct UploadBatch {
+    started_at: f64,
+    /// Bytes sent by the uploads of the batch that have finished.
+    finished_bytes: f64,
 }
 
 #[derive(Clone)]
@@ -61,10 +303,43 @@ pub enum ImageAnalysisViewMsg {
     NewImageUploaded(FileDetails),
     TimerTick,
     AnalysisRequestCompleted(usize, Result<HashMap<String, ImageAnalysisData>, String>),
-    DeleteImageRow(ImageAnalysisStatus),
+    DeleteImageRow(ImageId),
     StartUploadAll,
-    StartUpload(Rc<FileDetails>, ImageAnalysisOutcome),
-    FinishUpload(Rc<FileDetails>, Result<(), String>),
+    /// Upload an image, unless the archive has it already and the flag to force it is not set.
+    StartUpload(ImageId, bool),
+    /// Bytes of the upload sent so far, and its size.
+    UploadProgressed(ImageId, f64, f64),
+    AlreadyArchived(ImageId, SearchResult),
+    /// The upload finished, with the archive entry it created.
+    FinishUpload(ImageId, Result<Option<SearchResult>, String>),
+    SetReviewedLabel(ImageId, Option<String>),
+    /// A thumbnail rendered with the given transform.
+    ThumbnailReady(ImageId, Transform, BlobUrl),
+    ConfigureDownscale(DownscaleOptions),
+    ConfigurePrivacy(PrivacyOptions),
+    SetDuplicatePolicy(DuplicatePolicy),
+    PerceptualHashReady(ImageId, u64),
+    /// Collapse the other frames of the image's burst group.
+    KeepBestFrame(ImageId),
+    Expand(ImageId),
+    DismissSkippedDuplicates,
+    SetTags(ImageId, Vec<String>),
+    SetNote(ImageId, String),
+    SetCount(ImageId, u32),
+    SetSessionInfo(SessionInfo),
+    SetBulkTags(String),
+    /// Add the bulk tags to every image, or remove them if the flag is false.
+    ApplyBulkTags(bool),
+    /// An analysis copy rendered with the given transform.
+    AnalysisCopyReady(ImageId, Transform, AnalysisCopy),
+    /// Rotate or mirror an image by hand, and analyze it again.
+    SetTransform(ImageId, Transform),
+    SelectExporter(usize),
+    /// Replace the selected exporter with a reconfigured one.
+    ConfigureExporter(Rc<dyn Exporter>),
+    Export,
+    /// Generate the export; sent after `Export` had a chance to render the progress indicator.
+    GenerateExport,
 }
 
 impl Component for ImageAnalysisView {
@@ -79,94 +354,93 @@ impl Component for ImageAnalysisView {
                 link.send_message(ImageAnalysisViewMsg::TimerTick)
             })
         };
-        let s = Self {
+        let mut s = Self {
             requests_sent: 0,
             images: vec![],
+            next_image_id: 0,
+            on_delete_row: ctx.link().callback(ImageAnalysisViewMsg::DeleteImageRow),
+            on_upload_row: ctx
+                .link()
+                .callback(|(id, force)| ImageAnalysisViewMsg::StartUpload(id, force)),
+            on_review_row: ctx
+                .link()
+                .callback(|(id, label)| ImageAnalysisViewMsg::SetReviewedLabel(id, label)),
+            on_transform_row: ctx
+                .link()
+                .callback(|(id, transform)| ImageAnalysisViewMsg::SetTransform(id, transform)),
+            on_keep_frame_row: ctx.link().callback(ImageAnalysisViewMsg::KeepBestFrame),
+            on_expand_row: ctx.link().callback(ImageAnalysisViewMsg::Expand),
+            on_tags_row: ctx
+                .link()
+                .callback(|(id, tags)| ImageAnalysisViewMsg::SetTags(id, tags)),
+            on_note_row: ctx
+                .link()
+                .callback(|(id, note)| ImageAnalysisViewMsg::SetNote(id, note)),
+            on_count_row: ctx
+                .link()
+                .callback(|(id, count)| ImageAnalysisViewMsg::SetCount(id, count)),
             _clock_handle,
-            uploading: vec![],
             alerts: vec![],
+            exporters: all_exporters(),
+            selected_exporter: 0,
+            exporting: false,
+            downscale: DownscaleOptions::default(),
+            privacy: PrivacyOptions::default(),
+            duplicate_policy: DuplicatePolicy::Skip,
+            skipped_duplicates: vec![],
+            bulk_tags: String::new(),
+            session: SessionInfo::default(),
+            upload_batch: None,
         };
 
+        if let Some((queue, _)) = ctx.link().context::<AnalysisQueue>(Callback::noop()) {
+            for img in queue.take() {
+                s.on_image(ctx, img);
+            }
+        }
+
         s
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
-        let mut image_rows = vec![];
-        for image in self.images.iter() {
-            let img = image.clone();
-            let on_delete = ctx
-                .link()
-                .callback(move |_i| ImageAnalysisViewMsg::DeleteImageRow(img.clone()));
-            let img = image.clone();
-            let on_upload = ctx.link().callback(move |_i| {
-                ImageAnalysisViewMsg::StartUpload(img.clone().data, img.clone().outcome)
-            });
-
-            image_rows.push(html!(
-                <AnalysisReportRow image={image.clone()} {on_delete} {on_upload}/>
-            ));
-        }
+        let image_rows = self.images.iter().map(|image| {
+            html!(
+                <AnalysisReportRow
+                    key={image.id}
+                    image={image.clone()}
+                    on_delete={&self.on_delete_row}
+                    on_upload={&self.on_upload_row}
+                    on_review={&self.on_review_row}
+                    on_transform={&self.on_transform_row}
+                    on_keep_frame={&self.on_keep_frame_row}
+                    on_expand={&self.on_expand_row}
+                    on_tags={&self.on_tags_row}
+                    on_note={&self.on_note_row}
+                    on_count={&self.on_count_row}
+                />
+            )
+        });
 
         let on_image = ctx
             .link()
             .callback(|i| ImageAnalysisViewMsg::NewImageUploaded(i));
 
-        let uploading_state = if self.uploading.is_empty() {
-            html!()
-        } else {
-            html!(
-                <p class="mb-3">{format!("Uploading {} images...", self.uploading.len())}<div class="spinner-border" role="status"></div></p>
-            )
-        };
-
-        let mut json_labels = HashMap::new();
-        let mut csv_labels = String::from("name;class\n");
-        for img in self.images.iter() {
-            json_labels.insert(img.data.name.clone(), img.outcome.clone());
-            let label = match &img.outcome {
-                ImageAnalysisOutcome::Analyzed(res) => {
-                    let max = res
-                        .overall_classification
-                        .iter()
-                        .fold(("unknown", 0.0f64), |(ok, ov), (nk, nv)| {
-                            if nv > &ov {
-                                (nk, *nv)
-                            } else {
-                                (ok, ov)
-                            }
-                        })
-                        .0;
-                    max.to_string()
-                }
-                _ => "unknown".to_string(),
-            };
-            csv_labels.extend(format!("{};{}\n", img.data.name, label).chars());
-        }
-
-        let json_labels = serde_json::to_string(&json_labels).unwrap();
-        let json_labels = format!(
-            "data:application/json;base64,{}",
-            base64::engine::general_purpose::STANDARD.encode(json_labels)
-        );
-        let csv_labels = format!(
-            "data:text/csv;base64,{}",
-            base64::engine::general_purpose::STANDARD.encode(csv_labels)
-        );
-
         html! {
         <div>
+            {self.view_session(ctx)}
             <FileUploadBox {on_image} />
-            {uploading_state}
-            <div class="row mb-3">
-                <a href={json_labels} download="labels.json" class="btn btn-success col mx-2">{"Export all as JSON"}</a>
-                <a href={csv_labels} download="labels.csv" class="btn btn-primary col mx-2">{"Export labels only as CSV"}</a>
-            </div>
+            {self.view_duplicates(ctx)}
+            {self.view_downscale(ctx)}
+            {self.view_privacy(ctx)}
+            {self.view_bulk_tags(ctx)}
+            {self.view_upload_progress()}
+            {self.view_export(ctx)}
             <div class="row mb-3">
                 <button class="btn btn-warning col mx-2" onclick={ctx.link().callback(|_| ImageAnalysisViewMsg::StartUploadAll)}>{"Upload all to archive"}</button>
             </div>
             {for self.alerts.clone()}
             <div>
-                {image_rows}
+                { for image_rows }
             </div>
         </div>
         }
@@ -174,7 +448,7 @@ impl Component for ImageAnalysisView {
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
             ImageAnalysisViewMsg::NewImageUploaded(img) => {
-                self.on_image(img);
+                self.on_image(ctx, img);
                 true
             }
             ImageAnalysisViewMsg::TimerTick => self.collect_pending(ctx),
@@ -182,77 +456,250 @@ impl Component for ImageAnalysisView {
                 self.analysis_request_completed(idx, data);
                 true
             }
-            ImageAnalysisViewMsg::DeleteImageRow(img) => {
-                self.images.retain(|f| f != &img);
+            ImageAnalysisViewMsg::DeleteImageRow(id) => {
+                // What a deleted row already uploaded stays counted, so the batch's
+                // progress does not go backwards.
+                if let Some(ArchiveStatus::Uploading(progress)) =
+                    self.images.iter().find(|i| i.id == id).map(|i| &i.archive)
+                {
+                    if let Some(batch) = &mut self.upload_batch {
+                        batch.finished_bytes += progress.sent;
+                    }
+                }
+                self.images.retain(|f| f.id != id);
+                self.regroup();
                 true
             }
-            ImageAnalysisViewMsg::StartUpload(imgdata, imgoutcome) => {
-                self.uploading.push(imgdata.clone());
-                let max_tag = if let ImageAnalysisOutcome::Analyzed(res) = &imgoutcome {
-                    res.overall_classification
-                        .iter()
-                        .fold(("unknown", 0.0f64), |(ok, ov), (nk, nv)| {
-                            if nv > &ov {
-                                (nk, *nv)
-                            } else {
-                                (ok, ov)
-                            }
-                        })
-                        .0
-                        .to_string()
-                } else {
-                    "unknown_tag".to_string()
+            ImageAnalysisViewMsg::StartUpload(id, force) => {
+                let Some(img) = self.images.iter_mut().find(|i| i.id == id) else {
+                    return false;
                 };
-
-                ctx.link().send_future(async move {
-                    let client = reqwest::Client::new();
-                    let body = reqwest::multipart::Form::new()
-                        .part(
-                            "f[]",
-                            Part::bytes((&imgdata).data.clone())
-                                .file_name((&imgdata).name.clone())
-                                .mime_str(&imgdata.file_type)
-                                .unwrap(),
-                        )
-                        .part("tags", Part::text(max_tag).file_name(""))
-                        .part(
-                            "analysis",
-                            Part::text(serde_json::to_string(&imgoutcome).unwrap()),
-                        );
-
-                    let request = client.post(UPLOAD_URL).multipart(body).send().await;
-
-                    let result = match request {
-                        Ok(resp) => match resp.error_for_status() {
-                            Ok(_resp) => Ok(()),
-                            Err(why) => Err(format!("Error status in upload: {why}")),
-                        },
-                        Err(why) => Err(format!("Error sending upload request: {why}")),
+                if !img.archive.can_upload(force) {
+                    return false;
+                }
+                img.archive = ArchiveStatus::Queued { force };
+                self.upload_batch.get_or_insert_with(|| UploadBatch {
+                    started_at: js_sys::Date::now(),
+                    finished_bytes: 0.0,
+                });
+                self.start_queued_uploads(ctx);
+                true
+            }
+            ImageAnalysisViewMsg::UploadProgressed(id, sent, total) => {
+                let Some(img) = self.images.iter_mut().find(|i| i.id == id) else {
+                    return false;
+                };
+                let ArchiveStatus::Uploading(progress) = &mut img.archive else {
+                    return false;
+                };
+                progress.sent = sent;
+                progress.total = total;
+                true
+            }
+            ImageAnalysisViewMsg::AlreadyArchived(id, existing) => {
+                if let Some(img) = self.images.iter_mut().find(|i| i.id == id) {
+                    img.archive = ArchiveStatus::AlreadyArchived(existing);
+                }
+                self.start_queued_uploads(ctx);
+                true
+            }
+            ImageAnalysisViewMsg::FinishUpload(id, res) => {
+                // The row may have been deleted while uploading.
+                if let Some(img) = self.images.iter_mut().find(|i| i.id == id) {
+                    if let (ArchiveStatus::Uploading(progress), Some(batch)) =
+                        (&img.archive, &mut self.upload_batch)
+                    {
+                        batch.finished_bytes += if res.is_ok() {
+                            progress.total
+                        } else {
+                            progress.sent
+                        };
+                    }
+                    match res {
+                        Ok(entry) => {
+                            self.alerts.push(html!(
+                                <Alert style="success" text={format!("Successfully uploaded {}!", img.data.name)} />
+                            ));
+                            img.archive = ArchiveStatus::Archived(entry);
+                        }
+                        Err(why) => {
+                            self.alerts.push(html!(
+                                <Alert style="danger" text={format!("Failed to upload {}: {why}", img.data.name)} />
+                            ));
+                            img.archive = ArchiveStatus::Failed(why);
+                        }
                     };
-
-                    ImageAnalysisViewMsg::FinishUpload(imgdata, result)
+                }
+                self.start_queued_uploads(ctx);
+                true
+            }
+            ImageAnalysisViewMsg::SetReviewedLabel(id, label) => {
+                if let Some(img) = self.images.iter_mut().find(|i| i.id == id) {
+                    img.reviewed_label = label;
+                }
+                true
+            }
+            ImageAnalysisViewMsg::ThumbnailReady(id, transform, thumbnail) => {
+                // Ignore renderings that were overtaken by another rotation.
+                if let Some(img) = self
+                    .images
+                    .iter_mut()
+                    .find(|i| i.id == id && i.transform == transform)
+                {
+                    img.thumbnail = Some(thumbnail);
+                }
+                true
+            }
+            ImageAnalysisViewMsg::SetTransform(id, transform) => {
+                let Some(idx) = self.images.iter().position(|i| i.id == id) else {
+                    return false;
+                };
+                self.images[idx].transform = transform;
+                self.images[idx].outcome = ImageAnalysisOutcome::WaitingToSend;
+                self.images[idx].analyzed_at = None;
+                self.images[idx].analysis_copy = self.prepare(ctx, &self.images[idx]);
+                true
+            }
+            ImageAnalysisViewMsg::ConfigureDownscale(options) => {
+                self.downscale = options;
+                true
+            }
+            ImageAnalysisViewMsg::ConfigurePrivacy(options) => {
+                for img in self.images.iter_mut() {
+                    img.metadata_changes =
+                        privacy::planned_changes(&img.data.embedded, &img.data.exif, &options);
+                }
+                self.privacy = options;
+                true
+            }
+            ImageAnalysisViewMsg::SetDuplicatePolicy(policy) => {
+                self.duplicate_policy = policy;
+                true
+            }
+            ImageAnalysisViewMsg::PerceptualHashReady(id, hash) => {
+                if let Some(img) = self.images.iter_mut().find(|i| i.id == id) {
+                    img.dhash = Some(hash);
+                }
+                self.regroup();
+                true
+            }
+            ImageAnalysisViewMsg::KeepBestFrame(id) => {
+                let Some(group) = self
+                    .images
+                    .iter()
+                    .find(|i| i.id == id)
+                    .and_then(|i| i.burst)
+                else {
+                    return false;
+                };
+                for img in self.images.iter_mut() {
+                    if img.burst.map(|b| b.id) == Some(group.id) {
+                        img.collapsed = img.id != id;
+                    }
+                }
+                true
+            }
+            ImageAnalysisViewMsg::Expand(id) => {
+                if let Some(img) = self.images.iter_mut().find(|i| i.id == id) {
+                    img.collapsed = false;
+                }
+                true
+            }
+            ImageAnalysisViewMsg::DismissSkippedDuplicates => {
+                self.skipped_duplicates.clear();
+                true
+            }
+            ImageAnalysisViewMsg::SetTags(id, tags) => {
+                if let Some(img) = self.images.iter_mut().find(|i| i.id == id) {
+                    img.tags = tags;
+                }
+                true
+            }
+            ImageAnalysisViewMsg::SetNote(id, note) => {
+                if let Some(img) = self.images.iter_mut().find(|i| i.id == id) {
+                    img.note = note;
+                }
+                true
+            }
+            ImageAnalysisViewMsg::SetCount(id, count) => {
+                if let Some(img) = self.images.iter_mut().find(|i| i.id == id) {
+                    img.count = count;
+                }
+                true
+            }
+            ImageAnalysisViewMsg::SetSessionInfo(info) => {
+                self.session = info;
+                true
+            }
+            ImageAnalysisViewMsg::SetBulkTags(tags) => {
+                self.bulk_tags = tags;
+                true
+            }
+            ImageAnalysisViewMsg::ApplyBulkTags(add) => {
+                let bulk: Vec<String> = self
+                    .bulk_tags
+                    .split_whitespace()
+                    .map(String::from)
+                    .collect();
+                for img in self.images.iter_mut().filter(|i| !i.archive.is_sent()) {
+                    img.tags.retain(|t| !bulk.contains(t));
+                    if add {
+                        img.tags.extend(bulk.iter().cloned());
+                    }
+                }
+                true
+            }
+            ImageAnalysisViewMsg::AnalysisCopyReady(id, transform, copy) => {
+                if let Some(img) = self
+                    .images
+                    .iter_mut()
+                    .find(|i| i.id == id && i.transform == transform)
+                {
+                    img.analysis_copy = copy;
+                }
+                true
+            }
+            ImageAnalysisViewMsg::SelectExporter(idx) => {
+                self.selected_exporter = idx.min(self.exporters.len() - 1);
+                true
+            }
+            ImageAnalysisViewMsg::ConfigureExporter(exporter) => {
+                self.exporters[self.selected_exporter] = exporter;
+                true
+            }
+            ImageAnalysisViewMsg::Export => {
+                if self.exporting {
+                    return false;
+                }
+                self.exporting = true;
+                ctx.link().send_future(async {
+                    TimeoutFuture::new(0).await;
+                    ImageAnalysisViewMsg::GenerateExport
                 });
                 true
             }
-            ImageAnalysisViewMsg::FinishUpload(imgdata, res) => {
-                self.uploading.retain(|x| x != &imgdata);
-                match res {
-                    Ok(_) => {
-                        self.alerts.push(html!(
-                            <Alert style="success" text={format!("Successfully uploaded {}!", imgdata.name)} />
-                        ))
-                    },
-                    Err(why) => {
-                        self.alerts.push(html!(
-                            <Alert style="danger" text={format!("Failed to upload {}: {why}", imgdata.name)} />
-                        ))
-                    },
+            ImageAnalysisViewMsg::GenerateExport => {
+                self.exporting = false;
+                let exporter = &self.exporters[self.selected_exporter];
+                let session = ExportSession {
+                    images: &self.images,
+                    info: &self.session,
                 };
+                let output = exporter.write(&session);
+                download_blob(output.data, &exporter.file_name());
+                if let Some(summary) = output.summary {
+                    self.alerts
+                        .push(html!(<Alert style="info" text={summary} />));
+                }
                 true
-            },
+            }
             ImageAnalysisViewMsg::StartUploadAll => {
                 info!("Uploading all!!");
-                let msgs: Vec<ImageAnalysisViewMsg> = self.images.iter().map(|i: &ImageAnalysisStatus| ImageAnalysisViewMsg::StartUpload(i.data.clone(), i.outcome.clone())).collect();
+                let msgs: Vec<ImageAnalysisViewMsg> = self
+                    .images
+                    .iter()
+                    .map(|i: &ImageAnalysisStatus| ImageAnalysisViewMsg::StartUpload(i.id, false))
+                    .collect();
                 ctx.link().send_message_batch(msgs);
                 false
             }
@@ -260,14 +707,548 @@ impl Component for ImageAnalysisView {
     }
 }
 
+/// Re-encode an image for the classifier: shrunk and upright. Keeps the original if it
+/// is upright already and shrinking would not make it smaller.
+async fn copy_for_analysis(
+    image: &FileDetails,
+    transform: Transform,
+    rotated: bool,
+    options: &DownscaleOptions,
+) -> Result<AnalysisCopy, String> {
+    let blob = imaging::downscale(
+        &image.blob,
+        options.max_edge,
+        transform,
+        options.format,
+        options.quality,
+    )
+    .await?;
+    if !rotated && blob.size() >= image.size() {
+        return Ok(AnalysisCopy::Original);
+    }
+    // Browsers that can't encode the chosen format fall back to PNG, so go by the result.
+    Ok(AnalysisCopy::Reencoded {
+        file_type: blob.raw_mime_type(),
+        blob,
+    })
+}
+
+/// The file to archive and its name, with the metadata `options` asks to remove taken
+/// out. JPEGs are edited in place; other formats are re-encoded, which drops all of their
+/// metadata. Formats the browser can't write become PNGs, renamed to match.
+async fn archive_file(
+    image: &FileDetails,
+    options: &PrivacyOptions,
+) -> Result<(Blob, String), String> {
+    if privacy::planned_changes(&image.embedded, &image.exif, options).is_empty() {
+        return Ok((image.blob.clone(), image.name.clone()));
+    }
+    if privacy::needs_reencode(&image.embedded, &image.exif, options) {
+        let format = OutputFormat::from_mime_type(&image.file_type).unwrap_or(OutputFormat::Png);
+        let blob =
+            imaging::downscale(&image.blob, u32::MAX, Transform::default(), format, 0.95).await?;
+        // Browsers that can't encode WebP fall back to PNG.
+        let name = match OutputFormat::from_mime_type(&blob.raw_mime_type()) {
+            Some(written) if written.mime_type() != image.file_type => {
+                with_extension(&image.name, written.extension())
+            }
+            _ => image.name.clone(),
+        };
+        return Ok((blob, name));
+    }
+    let data = image.read().await?;
+    let blob = match privacy::strip_metadata(&data, &image.exif, options)? {
+        Some(data) => Blob::new_with_options(data.as_slice(), Some(&image.file_type)),
+        None => image.blob.clone(),
+    };
+    Ok((blob, image.name.clone()))
+}
+
+/// `name` with its extension replaced, or added if it has none.
+fn with_extension(name: &str, extension: &str) -> String {
+    let stem = match name.rfind('.') {
+        Some(dot) if dot > 0 => &name[..dot],
+        _ => name,
+    };
+    format!("{stem}.{extension}")
+}
+
+/// Upload the image to the archive along with the other form `fields`, and return its
+/// entry if the archive replied with one; any success status means it was saved.
+async fn save(
+    image: &FileDetails,
+    fields: Vec<(&'static str, String)>,
+    privacy: &PrivacyOptions,
+    on_progress: Callback<(f64, f64)>,
+) -> Result<Option<SearchResult>, String> {
+    let (file, name) = archive_file(image, privacy)
+        .await
+        .map_err(|why| format!("Could not remove metadata: {why}"))?;
+    let mut form = UploadForm::new()?.file("f[]", &file, &name)?;
+    for (name, value) in fields {
+        form = form.text(name, &value)?;
+    }
+    let response = upload::post(UPLOAD_URL, form, on_progress).await?;
+    let saved: Option<Vec<SearchResult>> = serde_json::from_str(&response).ok();
+    Ok(saved.and_then(|entries| entries.into_iter().next()))
+}
+
+/// Size of the WASM linear memory. It never shrinks, so this is also the peak so far.
+fn wasm_memory_bytes() -> u32 {
+    wasm_bindgen::memory()
+        .unchecked_into::<js_sys::WebAssembly::Memory>()
+        .buffer()
+        .unchecked_into::<js_sys::ArrayBuffer>()
+        .byte_length()
+}
+
 impl ImageAnalysisView {
-    fn on_image(&mut self, i: FileDetails) {
+    /// Start queued uploads while there are free slots.
+    fn start_queued_uploads(&mut self, ctx: &Context<Self>) {
+        let running = self
+            .images
+            .iter()
+            .filter(|i| matches!(i.archive, ArchiveStatus::Uploading(_)))
+            .count();
+        let next: Vec<(ImageId, bool)> = self
+            .images
+            .iter()
+            .filter_map(|i| match i.archive {
+                ArchiveStatus::Queued { force } => Some((i.id, force)),
+                _ => None,
+            })
+            .collect();
+        if running == 0 && next.is_empty() {
+            self.upload_batch = None;
+        }
+        for (id, force) in next
+            .into_iter()
+            .take(MAX_CONCURRENT_UPLOADS.saturating_sub(running))
+        {
+            self.begin_upload(ctx, id, force);
+        }
+    }
+
+    fn begin_upload(&mut self, ctx: &Context<Self>, id: ImageId, force: bool) {
+        let Some(img) = self.images.iter_mut().find(|i| i.id == id) else {
+            return;
+        };
+        img.archive = ArchiveStatus::Uploading(UploadProgress::new(img.data.size() as f64));
+        let imgdata = img.data.clone();
+        let (latitude, longitude) = privacy::redact_coordinates(
+            self.session.latitude,
+            self.session.longitude,
+            &self.privacy,
+        );
+        let session = SessionInfo {
+            latitude,
+            longitude,
+            ..self.session.clone()
+        };
+        let exif = privacy::redact(&imgdata.exif, &self.privacy);
+        let fields = vec![
+            ("sha256", imgdata.sha256.clone()),
+            ("tags", img.archive_tags().join(" ")),
+            ("note", img.note.clone()),
+            ("count", img.count.to_string()),
+            ("session", serde_json::to_string(&session).unwrap()),
+            ("analysis", serde_json::to_string(&img.outcome).unwrap()),
+            ("exif", serde_json::to_string(&exif.record()).unwrap()),
+        ];
+        let privacy = self.privacy.clone();
+        let on_progress = ctx
+            .link()
+            .callback(move |(sent, total)| ImageAnalysisViewMsg::UploadProgressed(id, sent, total));
+
+        ctx.link().send_future(async move {
+            if !force {
+                match api::find_by_hash(&imgdata.sha256).await {
+                    Ok(found) if !found.is_empty() => {
+                        let existing = found.into_iter().next().unwrap();
+                        return ImageAnalysisViewMsg::AlreadyArchived(id, existing);
+                    }
+                    Ok(_) => {}
+                    Err(why) => {
+                        log::warn!("Could not check the archive for {}: {why}", imgdata.name)
+                    }
+                }
+            }
+            let result = save(&imgdata, fields, &privacy, on_progress).await;
+            ImageAnalysisViewMsg::FinishUpload(id, result)
+        });
+    }
+
+    fn view_upload_progress(&self) -> Html {
+        let Some(batch) = &self.upload_batch else {
+            return html!();
+        };
+        let (mut sent, mut total) = (batch.finished_bytes, batch.finished_bytes);
+        let (mut running, mut queued) = (0, 0);
+        for img in self.images.iter() {
+            match &img.archive {
+                ArchiveStatus::Uploading(progress) => {
+                    sent += progress.sent;
+                    total += progress.total;
+                    running += 1;
+                }
+                ArchiveStatus::Queued { .. } => {
+                    total += img.data.size() as f64;
+                    queued += 1;
+                }
+                _ => {}
+            }
+        }
+        let percent = upload::fraction(sent, total) * 100.0;
+        html! {
+            <div class="card mb-3">
+                <div class="card-body">
+                    <h6 class="card-title">{format!("Uploading {running} images, {queued} waiting")}</h6>
+                    <div class="progress mb-1" role="progressbar">
+                        <div class="progress-bar progress-bar-striped progress-bar-animated" style={format!("width: {percent:.1}%")}></div>
+                    </div>
+                    <small class="text-muted">{upload::describe(sent, total, batch.started_at)}</small>
+                </div>
+            </div>
+        }
+    }
+
+    fn view_duplicates(&self, ctx: &Context<Self>) -> Html {
+        let onchange = ctx.link().callback(|ev: Event| {
+            let select: HtmlSelectElement = ev.target_unchecked_into();
+            let policy = select
+                .value()
+                .parse()
+                .ok()
+                .and_then(|i: usize| DuplicatePolicy::ALL.get(i).copied())
+                .unwrap_or(DuplicatePolicy::Skip);
+            ImageAnalysisViewMsg::SetDuplicatePolicy(policy)
+        });
+        let skipped = if self.skipped_duplicates.is_empty() {
+            html!()
+        } else {
+            html! {
+                <div class="alert alert-info alert-dismissible">
+                    {format!(
+                        "Skipped {} files already in the session: {}",
+                        self.skipped_duplicates.len(),
+                        self.skipped_duplicates.join(", ")
+                    )}
+                    <button type="button" class="btn-close" onclick={ctx.link().callback(|_| ImageAnalysisViewMsg::DismissSkippedDuplicates)} />
+                </div>
+            }
+        };
+        html! {
+            <>
+                <div class="input-group mb-3">
+                    <label class="input-group-text">{"When a file is already in the session"}</label>
+                    <select class="form-select" {onchange}>
+                        { for DuplicatePolicy::ALL.iter().enumerate().map(|(i, policy)| html!(
+                            <option value={i.to_string()} selected={*policy == self.duplicate_policy}>{policy.label()}</option>
+                        )) }
+                    </select>
+                </div>
+                {skipped}
+            </>
+        }
+    }
+
+    fn view_downscale(&self, ctx: &Context<Self>) -> Html {
+        let (original, sent) = self
+            .images
+            .iter()
+            .filter_map(|img| match &img.analysis_copy {
+                AnalysisCopy::Reencoded { blob, .. } => Some((img.data.size(), blob.size())),
+                _ => None,
+            })
+            .fold((0, 0), |(o, s), (original, sent)| (o + original, s + sent));
+        let saved = if original > 0 {
+            html!(
+                <p class="mb-0 mt-2 text-muted">{format!(
+                    "Sent {:.1} MB for analysis instead of {:.1} MB, saving {:.0}%",
+                    sent as f64 / 1e6,
+                    original as f64 / 1e6,
+                    100.0 * (1.0 - sent as f64 / original as f64)
+                )}</p>
+            )
+        } else {
+            html!()
+        };
+        html! {
+            <div class="card mb-3">
+                <div class="card-body">
+                    <DownscaleOptionsForm
+                        options={self.downscale.clone()}
+                        on_change={ctx.link().callback(ImageAnalysisViewMsg::ConfigureDownscale)}
+                    />
+                    {saved}
+                </div>
+            </div>
+        }
+    }
+
+    fn view_privacy(&self, ctx: &Context<Self>) -> Html {
+        let changes = self.images.iter().map(|img| &img.metadata_changes);
+        let affected = changes.clone().filter(|c| !c.is_empty()).count();
+        let removed = privacy::tally(changes.clone().map(|c| c.removed.as_slice()));
+        let coarsened = privacy::tally(changes.map(|c| c.coarsened.as_slice()));
+        let list = |counts: BTreeMap<&str, usize>| {
+            counts
+                .into_iter()
+                .map(|(field, n)| format!("{field} ({n})"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let preview = if affected == 0 {
+            html!(<p class="mb-0 mt-2 text-muted">{"Uploads will not change any metadata."}</p>)
+        } else {
+            html! {
+                <div class="mt-2 small">
+                    <p class="mb-1">{format!("Uploading will edit the metadata of {affected} images:")}</p>
+                    if !removed.is_empty() {
+                        <p class="mb-1"><strong>{"Removed: "}</strong>{list(removed)}</p>
+                    }
+                    if !coarsened.is_empty() {
+                        <p class="mb-0"><strong>{"Rounded: "}</strong>{list(coarsened)}</p>
+                    }
+                </div>
+            }
+        };
+        html! {
+            <div class="card mb-3">
+                <div class="card-body">
+                    <h6 class="card-title">{"Privacy of archived photos"}</h6>
+                    <PrivacyOptionsForm
+                        options={self.privacy.clone()}
+                        on_change={ctx.link().callback(ImageAnalysisViewMsg::ConfigurePrivacy)}
+                    />
+                    {preview}
+                </div>
+            </div>
+        }
+    }
+
+    fn view_session(&self, ctx: &Context<Self>) -> Html {
+        let counts = session::species_counts(&self.images);
+        let summary = if counts.is_empty() {
+            html!()
+        } else {
+            html! {
+                <table class="table table-sm mt-3 mb-0">
+                    <thead><tr><th>{"Species"}</th><th>{"Individuals"}</th></tr></thead>
+                    { for counts.iter().map(|(label, n)| html!(
+                        <tr><td>{label}</td><td>{n}</td></tr>
+                    )) }
+                    <tr class="fw-bold"><td>{"Total"}</td><td>{counts.values().sum::<u32>()}</td></tr>
+                </table>
+            }
+        };
+        html! {
+            <div class="card mb-3">
+                <div class="card-body">
+                    <h6 class="card-title">{"Observation session"}</h6>
+                    <SessionInfoForm
+                        info={self.session.clone()}
+                        on_change={ctx.link().callback(ImageAnalysisViewMsg::SetSessionInfo)}
+                    />
+                    {summary}
+                </div>
+            </div>
+        }
+    }
+
+    fn view_bulk_tags(&self, ctx: &Context<Self>) -> Html {
+        let oninput = ctx.link().callback(|ev: InputEvent| {
+            let input: HtmlInputElement = ev.target_unchecked_into();
+            ImageAnalysisViewMsg::SetBulkTags(input.value())
+        });
+        let disabled = self.images.is_empty() || self.bulk_tags.trim().is_empty();
+        html! {
+            <div class="card mb-3">
+                <div class="card-body">
+                    <h6 class="card-title">{"Tags for all images"}</h6>
+                    <div class="input-group">
+                        <input type="text" class="form-control" placeholder="e.g. lake_north adult feeding" value={self.bulk_tags.clone()} {oninput} />
+                        <button class="btn btn-outline-primary" {disabled} onclick={ctx.link().callback(|_| ImageAnalysisViewMsg::ApplyBulkTags(true))}>{"Add to all"}</button>
+                        <button class="btn btn-outline-warning" {disabled} onclick={ctx.link().callback(|_| ImageAnalysisViewMsg::ApplyBulkTags(false))}>{"Remove from all"}</button>
+                    </div>
+                </div>
+            </div>
+        }
+    }
+
+    fn view_export(&self, ctx: &Context<Self>) -> Html {
+        let exporter = &self.exporters[self.selected_exporter];
+        let onchange = ctx.link().callback(|ev: Event| {
+            let select: HtmlSelectElement = ev.target_unchecked_into();
+            ImageAnalysisViewMsg::SelectExporter(select.value().parse().unwrap_or(0))
+        });
+        html! {
+            <div class="card mb-3">
+                <div class="card-body">
+                    <div class="input-group mb-2">
+                        <label class="input-group-text">{"Export format"}</label>
+                        <select class="form-select" {onchange}>
+                            { for self.exporters.iter().enumerate().map(|(i, e)| html!(
+                                <option value={i.to_string()} selected={i == self.selected_exporter}>{e.name()}</option>
+                            )) }
+                        </select>
+                        <button class="btn btn-success" disabled={self.exporting} onclick={ctx.link().callback(|_| ImageAnalysisViewMsg::Export)}>
+                            if self.exporting {
+                                <span class="spinner-border spinner-border-sm me-2" role="status"></span>{"Preparing export..."}
+                            } else {
+                                {format!("Export {}", exporter.file_name())}
+                            }
+                        </button>
+                    </div>
+                    {exporter.view_options(ctx.link().callback(ImageAnalysisViewMsg::ConfigureExporter))}
+                </div>
+            </div>
+        }
+    }
+
+    fn on_image(&mut self, ctx: &Context<Self>, i: FileDetails) {
         log::info!("Received image {}", i.name);
-        let status = ImageAnalysisStatus {
+        let id = self.next_image_id;
+        self.next_image_id += 1;
+        let metadata_changes = privacy::planned_changes(&i.embedded, &i.exif, &self.privacy);
+        let mut status = ImageAnalysisStatus {
+            id,
             data: Rc::new(i),
             outcome: ImageAnalysisOutcome::WaitingToSend,
+            added_at: js_sys::Date::now(),
+            analyzed_at: None,
+            reviewed_label: None,
+            thumbnail: None,
+            transform: Transform::default(),
+            analysis_copy: AnalysisCopy::Original,
+            duplicate_of: None,
+            dhash: None,
+            burst: None,
+            collapsed: false,
+            archive: ArchiveStatus::NotUploaded,
+            metadata_changes,
+            tags: vec![],
+            note: String::new(),
+            count: 1,
         };
+        if let Some(earlier) = self
+            .images
+            .iter()
+            .find(|img| img.data.sha256 == status.data.sha256)
+        {
+            info!(
+                "{} has the same contents as {}",
+                status.data.name, earlier.data.name
+            );
+            match self.duplicate_policy {
+                DuplicatePolicy::Skip => {
+                    self.skipped_duplicates.push(status.data.name.clone());
+                    return;
+                }
+                DuplicatePolicy::KeepBoth => status.duplicate_of = Some(earlier.data.name.clone()),
+                DuplicatePolicy::Replace => {
+                    let sha256 = status.data.sha256.clone();
+                    self.images.retain(|img| img.data.sha256 != sha256);
+                    self.regroup();
+                }
+            }
+        }
+        status.analysis_copy = self.prepare(ctx, &status);
         self.images.push(status);
+        debug!(
+            "{} images in session, WASM memory at {:.1} MiB",
+            self.images.len(),
+            wasm_memory_bytes() as f64 / (1024.0 * 1024.0)
+        );
+    }
+
+    /// Recompute the burst groups from the perceptual hashes. Each image joins the first
+    /// group whose first image looks like it, so that a slow pan can't chain frames that
+    /// look nothing alike into one group.
+    fn regroup(&mut self) {
+        // The first image of each group, and the group of each image that has a hash.
+        let mut firsts: Vec<usize> = vec![];
+        let mut groups: Vec<Option<usize>> = vec![None; self.images.len()];
+        for (i, img) in self.images.iter().enumerate() {
+            let Some(hash) = img.dhash else {
+                continue;
+            };
+            let near = firsts.iter().position(|&first| {
+                let first_hash = self.images[first].dhash.unwrap_or_default();
+                (first_hash ^ hash).count_ones() <= NEAR_DUPLICATE_DISTANCE
+            });
+            groups[i] = Some(near.unwrap_or_else(|| {
+                firsts.push(i);
+                firsts.len() - 1
+            }));
+        }
+        let ids: Vec<ImageId> = firsts.iter().map(|&first| self.images[first].id).collect();
+        let mut sizes = vec![0; firsts.len()];
+        for group in groups.iter().flatten() {
+            sizes[*group] += 1;
+        }
+        for (img, group) in self.images.iter_mut().zip(groups) {
+            let burst = group.filter(|g| sizes[*g] > 1).map(|g| BurstGroup {
+                id: ids[g],
+                size: sizes[g],
+            });
+            if burst.is_none() {
+                img.collapsed = false;
+            }
+            img.burst = burst;
+        }
+    }
+
+    /// Start rendering the thumbnail and, if the original file cannot be sent as is,
+    /// the copy to analyze. Returns the analysis copy to use until then.
+    fn prepare(&self, ctx: &Context<Self>, img: &ImageAnalysisStatus) -> AnalysisCopy {
+        let id = img.id;
+        let transform = img.transform;
+        {
+            let data = img.data.clone();
+            let needs_hash = img.dhash.is_none();
+            ctx.link().send_future_batch(async move {
+                match imaging::thumbnail(&data.blob, transform).await {
+                    Ok((thumbnail, hash)) => {
+                        let mut msgs = vec![ImageAnalysisViewMsg::ThumbnailReady(
+                            id, transform, thumbnail,
+                        )];
+                        if needs_hash {
+                            msgs.push(ImageAnalysisViewMsg::PerceptualHashReady(id, hash));
+                        }
+                        msgs
+                    }
+                    Err(why) => {
+                        log::warn!("No thumbnail for {}: {why}", data.name);
+                        vec![]
+                    }
+                }
+            });
+        }
+
+        // The classifier ignores EXIF, so rotated photos are sent re-encoded upright.
+        let rotated = !transform.is_identity() || img.data.exif.orientation.unwrap_or(1) != 1;
+        let options = match (self.downscale.enabled, rotated) {
+            (true, _) => self.downscale.clone(),
+            // Turned upright at full size.
+            (false, true) => DownscaleOptions {
+                max_edge: u32::MAX,
+                ..self.downscale.clone()
+            },
+            (false, false) => return AnalysisCopy::Original,
+        };
+        let data = img.data.clone();
+        ctx.link().send_future(async move {
+            let copy = match copy_for_analysis(&data, transform, rotated, &options).await {
+                Ok(copy) => copy,
+                Err(why) => {
+                    log::warn!("Sending {} as is: {why}", data.name);
+                    AnalysisCopy::Original
+                }
+            };
+            ImageAnalysisViewMsg::AnalysisCopyReady(id, transform, copy)
+        });
+        AnalysisCopy::Pending
     }
 
     fn analysis_request_completed(
@@ -284,6 +1265,7 @@ impl ImageAnalysisView {
                     continue;
                 }
                 info!("Updated {}", item.data.name);
+                item.analyzed_at = Some(js_sys::Date::now());
                 if let Ok(file_data) = &data {
                     // Find the item with this file name in the response
                     if let Some(item_data) = file_data.get(&item.data.name) {
@@ -309,7 +1291,9 @@ impl ImageAnalysisView {
             let mut to_send = vec![];
             for img in self.images.iter_mut() {
                 //debug!("Found: {}", img.data.name);
-                if matches!(img.outcome, ImageAnalysisOutcome::WaitingToSend) {
+                if matches!(img.outcome, ImageAnalysisOutcome::WaitingToSend)
+                    && img.analysis_copy != AnalysisCopy::Pending
+                {
                     info!("Found pending: {}", (&img).data.name);
                     to_send.push(img);
                 }
@@ -344,36 +1328,20 @@ impl ImageAnalysisView {
 
         for (request_idx, out_batch) in to_send {
             ctx.link().send_future(async move {
-                let client = reqwest::Client::new();
-                let mut body = reqwest::multipart::Form::new();
-                for img in out_batch {
-                    body = body.part(
-                        "f[]",
-                        Part::bytes(img.data.data.clone())
-                            .file_name(img.data.name.clone())
-                            .mime_str(&img.data.file_type)
-                            .unwrap(),
-                    );
-                }
-
                 info!("Sending request {request_idx}");
-                let request = client.post(ANALYSIS_URL).multipart(body).send().await;
-                let request_outcome = if let Ok(resp) = request {
-                    let data: Result<AnalysisResponse, _> = resp.json().await;
-                    if let Ok(data) = data {
-                        Ok(data)
-                    } else {
-                        Err(format!(
-                            "Data returned was not valid JSON: {}",
-                            data.unwrap_err()
-                        ))
+                let mut files = vec![];
+                for img in out_batch.iter() {
+                    match img.analysis_part().await {
+                        Ok(part) => files.push(part),
+                        Err(why) => {
+                            return ImageAnalysisViewMsg::AnalysisRequestCompleted(
+                                request_idx,
+                                Err(why),
+                            )
+                        }
                     }
-                } else {
-                    Err(format!(
-                        "Error while sending request: {}",
-                        request.unwrap_err()
-                    ))
-                };
+                }
+                let request_outcome = api::analyze(files).await;
                 info!("Received response for {request_idx}: {request_outcome:?}");
                 ImageAnalysisViewMsg::AnalysisRequestCompleted(request_idx, request_outcome)
             });
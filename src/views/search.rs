@@ -1,20 +1,23 @@
+use std::collections::HashSet;
+use std::rc::Rc;
+
 use log::debug;
 use wasm_bindgen::JsCast;
 use wasm_bindgen::UnwrapThrowExt;
-use web_sys::HtmlInputElement;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
 use yew::prelude::*;
+use yew_router::prelude::*;
 
+use crate::api::{self, SearchResult};
 use crate::components::alert::Alert;
-
-#[derive(serde::Deserialize)]
-pub struct SearchResult {
-    filename: String,
-    delete: String,
-    download: String,
-    uuid: String,
-    update: String,
-    tags: Vec<String>,
-}
+use crate::components::file_upload_box::{FileDetails, FileUploadBox};
+use crate::components::image_display::ImageDisplayBox;
+use crate::components::reanalysis_diff::{ReanalysisDiff, ReanalysisEntry};
+use crate::components::tag_cloud::{TagCloud, TagCounts};
+use crate::download::download_blob;
+use crate::views::image_analysis::AnalysisQueue;
+use crate::zip::{sanitize_path_component, ZipWriter};
+use crate::Route;
 
 pub enum SearchState {
     Results(Vec<SearchResult>),
@@ -22,15 +25,200 @@ pub enum SearchState {
     Error(String),
 }
 
+const RESULTS_PER_PAGE: usize = 24;
+const MAX_SUGGESTIONS: usize = 8;
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum SortOrder {
+    /// Keep the order the server returned the results in.
+    #[default]
+    #[serde(rename = "relevance")]
+    Relevance,
+    #[serde(rename = "name")]
+    NameAsc,
+    #[serde(rename = "-name")]
+    NameDesc,
+}
+
+impl SortOrder {
+    const ALL: [SortOrder; 3] = [
+        SortOrder::Relevance,
+        SortOrder::NameAsc,
+        SortOrder::NameDesc,
+    ];
+
+    fn key(&self) -> &'static str {
+        match self {
+            SortOrder::Relevance => "relevance",
+            SortOrder::NameAsc => "name",
+            SortOrder::NameDesc => "-name",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            SortOrder::Relevance => "Relevance",
+            SortOrder::NameAsc => "File name (A-Z)",
+            SortOrder::NameDesc => "File name (Z-A)",
+        }
+    }
+
+    fn from_key(key: &str) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|s| s.key() == key)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum SearchMode {
+    #[default]
+    #[serde(rename = "tags")]
+    Tags,
+    /// Query-by-example: find images similar to an uploaded one.
+    #[serde(rename = "image")]
+    Image,
+}
+
+/// The search state that is kept in the URL query string,
+/// so that searches can be shared, bookmarked and navigated with the back button.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct SearchParams {
+    #[serde(default)]
+    pub mode: SearchMode,
+    #[serde(default)]
+    pub q: String,
+    #[serde(default)]
+    pub sort: SortOrder,
+    #[serde(default = "first_page")]
+    pub page: usize,
+}
+
+fn first_page() -> usize {
+    1
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        Self {
+            mode: SearchMode::default(),
+            q: String::new(),
+            sort: SortOrder::default(),
+            page: first_page(),
+        }
+    }
+}
+
+impl SearchParams {
+    fn from_location(location: &Location) -> Self {
+        location.query::<SearchParams>().unwrap_or_default()
+    }
+}
+
 pub struct Search {
     state: SearchState,
     query: String,
+    params: SearchParams,
+    /// The image being searched for in query-by-example mode.
+    example: Option<Rc<FileDetails>>,
+    tags: Result<Rc<TagCounts>, String>,
+    show_tag_cloud: bool,
+    /// UUIDs of the selected results; kept across pages and sort orders.
+    selected: HashSet<String>,
+    /// The last result whose checkbox was clicked, the anchor for shift-click ranges.
+    selection_anchor: Option<String>,
+    retag_input: String,
+    running_batch: Option<BatchAction>,
+    reanalysis: Option<Rc<Vec<ReanalysisEntry>>>,
+    alerts: Vec<Html>,
+    _location_handle: Option<LocationHandle>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BatchAction {
+    AddTags(Vec<String>),
+    ReplaceTags(Vec<String>),
+    Delete,
+    DownloadZip,
+    Reclassify,
+    Reanalyze,
+}
+
+impl BatchAction {
+    fn describe(&self) -> &'static str {
+        match self {
+            BatchAction::AddTags(_) => "Adding tags to",
+            BatchAction::ReplaceTags(_) => "Retagging",
+            BatchAction::Delete => "Deleting",
+            BatchAction::DownloadZip => "Downloading",
+            BatchAction::Reclassify => "Fetching for re-classification",
+            BatchAction::Reanalyze => "Re-analyzing",
+        }
+    }
+
+    /// The question to confirm before running a destructive action on `count` images.
+    fn confirmation(&self, count: usize) -> Option<String> {
+        match self {
+            BatchAction::ReplaceTags(new) => Some(format!(
+                "Replace the tags of {count} images with \"{}\"? Their current tags will be lost.",
+                new.join(" ")
+            )),
+            BatchAction::Delete => Some(format!(
+                "Delete {count} images from the archive? This cannot be undone."
+            )),
+            _ => None,
+        }
+    }
+
+    /// The tags an item should have after this action, if it changes them.
+    fn apply_tags(&self, old: &[String]) -> Option<Vec<String>> {
+        match self {
+            BatchAction::AddTags(new) => {
+                let mut tags = old.to_vec();
+                for tag in new {
+                    if !tags.contains(tag) {
+                        tags.push(tag.clone());
+                    }
+                }
+                Some(tags)
+            }
+            BatchAction::ReplaceTags(new) => Some(new.clone()),
+            _ => None,
+        }
+    }
 }
 
 pub enum SearchMsg {
     SetSearchQuery(String),
     RunSearch,
     RecvResults(Result<Vec<SearchResult>, String>),
+    SetSort(SortOrder),
+    SetMode(SearchMode),
+    SearchByExample(Box<FileDetails>),
+    SetPage(usize),
+    LocationChanged(Location),
+    RecvTags(Result<TagCounts, String>),
+    /// Replace the word currently being typed with this tag.
+    CompleteTag(String),
+    /// Append this tag to the query and search for it.
+    AddTag(String),
+    ToggleTagCloud,
+    /// A result's checkbox was clicked; with shift held, the range from the last click is toggled.
+    ToggleSelected {
+        uuid: String,
+        shift: bool,
+    },
+    SelectPage,
+    ClearSelection,
+    SetRetagInput(String),
+    RunBatch(BatchAction),
+    BatchFinished(BatchAction, Vec<(String, Result<(), String>)>),
+    ReanalysisFinished(Vec<ReanalysisEntry>),
+    /// Swap the stored label tag for the new one on these re-analyzed images.
+    UpdateReanalyzedTags(Vec<String>),
+    ReanalyzedTagsUpdated(Vec<(String, Vec<String>, Result<(), String>)>),
+    CloseReanalysis,
 }
 
 fn get_text(e: InputEvent) -> String {
@@ -40,41 +228,81 @@ fn get_text(e: InputEvent) -> String {
     target.value()
 }
 
-use crate::root_url;
-
-const SEARCH_URL: &str = concat!(root_url!(), "/image");
-
-
 impl Component for Search {
     type Message = SearchMsg;
 
     type Properties = ();
 
     fn create(ctx: &Context<Self>) -> Self {
-        Self {
+        let params = ctx
+            .link()
+            .location()
+            .map(|l| SearchParams::from_location(&l))
+            .unwrap_or_default();
+        let _location_handle = ctx
+            .link()
+            .add_location_listener(ctx.link().callback(SearchMsg::LocationChanged));
+
+        let mut s = Self {
             state: SearchState::Results(vec![]),
-            query: "".to_string(),
+            query: params.q.clone(),
+            params,
+            example: None,
+            tags: Ok(Rc::default()),
+            show_tag_cloud: false,
+            selected: HashSet::new(),
+            selection_anchor: None,
+            retag_input: String::new(),
+            running_batch: None,
+            reanalysis: None,
+            alerts: vec![],
+            _location_handle,
+        };
+        if s.params.mode == SearchMode::Tags && !s.params.q.is_empty() {
+            s.fetch_results(ctx);
         }
+        Self::fetch_tags(ctx);
+        s
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
         let results = match &self.state {
             SearchState::Results(items) => {
+                let (page_items, page, page_count) = self.current_page();
                 let mut imgs = vec![];
-                for item in items {
+                for item in page_items {
+                    let is_selected = self.selected.contains(&item.uuid);
+                    let onclick = {
+                        let uuid = item.uuid.clone();
+                        ctx.link()
+                            .callback(move |ev: MouseEvent| SearchMsg::ToggleSelected {
+                                uuid: uuid.clone(),
+                                shift: ev.shift_key(),
+                            })
+                    };
                     imgs.push(html!(
-                        <div class="card col">
+                        <div class={classes!("card", "col", is_selected.then_some("border-primary"))}>
+                            <div class="form-check mt-2">
+                                <input class="form-check-input" type="checkbox" checked={is_selected} {onclick} />
+                            </div>
                             <img class="img-card-top" src={item.download.clone()} />
-                            <p class="card-footer">{item.filename.clone()}</p>
+                            <p class="card-footer">
+                                {item.filename.clone()}<br/>
+                                { for item.score.map(|score| html!(<span class="badge text-bg-info me-1">{format!("similarity {:.0}%", score * 100.0)}</span>)) }
+                                { for item.tags.iter().map(|t| html!(<span class="badge text-bg-secondary me-1">{t}</span>)) }
+                                { for item.note.iter().filter(|n| !n.is_empty()).map(|n| html!(<><br/><small class="text-muted">{n}</small></>)) }
+                            </p>
                         </div>
                     ));
                 }
                 html!(
                     <>
                         <p>{format!("Showing {} results", items.len())}</p>
+                        {self.view_batch_toolbar(ctx)}
                         <div class="row row-cols-4">
                             {imgs}
                         </div>
+                        {self.view_pagination(ctx, page, page_count)}
                     </>
                 )
             }
@@ -88,14 +316,28 @@ impl Component for Search {
 
         html! {
             <div class="container">
+                <ul class="nav nav-tabs mb-3">
+                    {self.mode_tab(ctx, SearchMode::Tags, "By tags")}
+                    {self.mode_tab(ctx, SearchMode::Image, "By example image")}
+                </ul>
                 <div class="row">
                     <div class="col">
-                        <div class="input-group mb-3">
-                            <input type="text" class="form-control" placeholder="tag1 tag2 tag3 ..." oninput={ctx.link().callback(|ev: InputEvent| SearchMsg::SetSearchQuery(get_text(ev)))}/>
-                            <button class="btn btn-outline-primary" type="button" onclick={ctx.link().callback(|_| SearchMsg::RunSearch)}>{"Search"}</button>
-                        </div>
+                        {self.view_query_input(ctx)}
+                    </div>
+                    <div class="col-3">
+                        <select class="form-select" onchange={ctx.link().callback(|ev: Event| {
+                            let select: HtmlSelectElement = ev.target_unchecked_into();
+                            SearchMsg::SetSort(SortOrder::from_key(&select.value()))
+                        })}>
+                            { for SortOrder::ALL.iter().map(|s| html!(
+                                <option value={s.key()} selected={*s == self.params.sort}>{s.label()}</option>
+                            )) }
+                        </select>
                     </div>
                 </div>
+                {self.view_tag_cloud(ctx)}
+                {for self.alerts.clone()}
+                {self.view_reanalysis(ctx)}
                 <div class="row">
                     {results}
                 </div>
@@ -108,36 +350,246 @@ impl Component for Search {
             SearchMsg::SetSearchQuery(s) => {
                 debug!("Now searching for {s:?}");
                 self.query = s;
-                false
+                true
             }
             SearchMsg::RunSearch => {
+                self.params = SearchParams {
+                    q: self.query.trim().to_string(),
+                    page: first_page(),
+                    ..self.params.clone()
+                };
+                self.push_params(ctx);
+                self.fetch_results(ctx);
+                true
+            }
+            SearchMsg::SetSort(sort) => {
+                self.params.sort = sort;
+                self.params.page = first_page();
+                self.push_params(ctx);
+                true
+            }
+            SearchMsg::SetMode(mode) => {
+                if mode == self.params.mode {
+                    return false;
+                }
+                self.params = SearchParams {
+                    mode,
+                    ..SearchParams::default()
+                };
+                self.query.clear();
+                self.example = None;
+                self.state = SearchState::Results(vec![]);
+                self.push_params(ctx);
+                true
+            }
+            SearchMsg::SearchByExample(file) => {
+                let file = Rc::new(*file);
+                self.example = Some(file.clone());
+                self.params.page = first_page();
                 self.state = SearchState::Running;
-                let query = self.query.replace(" ", "+");
+                ctx.link()
+                    .send_future(async move { SearchMsg::RecvResults(api::similar(&file).await) });
+                true
+            }
+            SearchMsg::SetPage(page) => {
+                self.params.page = page;
+                self.push_params(ctx);
+                true
+            }
+            SearchMsg::RecvTags(res) => {
+                self.tags = res.map(Rc::new);
+                true
+            }
+            SearchMsg::CompleteTag(tag) => {
+                let mut words: Vec<&str> = self.query.split_whitespace().collect();
+                if !self.query.ends_with(char::is_whitespace) {
+                    words.pop();
+                }
+                words.push(&tag);
+                self.query = words.join(" ") + " ";
+                true
+            }
+            SearchMsg::AddTag(tag) => {
+                if !self.query.split_whitespace().any(|w| w == tag) {
+                    self.query = format!("{} {tag}", self.query.trim());
+                }
+                ctx.link().send_message(SearchMsg::RunSearch);
+                false
+            }
+            SearchMsg::ToggleTagCloud => {
+                self.show_tag_cloud = !self.show_tag_cloud;
+                true
+            }
+            SearchMsg::LocationChanged(location) => {
+                let params = SearchParams::from_location(&location);
+                if params == self.params {
+                    return false;
+                }
+                let mode_changed = params.mode != self.params.mode;
+                let query_changed = params.q != self.params.q;
+                self.query = params.q.clone();
+                self.params = params;
+                match self.params.mode {
+                    SearchMode::Tags if mode_changed || query_changed => {
+                        if self.params.q.is_empty() {
+                            self.state = SearchState::Results(vec![]);
+                        } else {
+                            self.fetch_results(ctx);
+                        }
+                    }
+                    // The example image can't be kept in the URL, so it has to be dropped again.
+                    SearchMode::Image if mode_changed => {
+                        self.example = None;
+                        self.state = SearchState::Results(vec![]);
+                    }
+                    _ => {}
+                }
+                true
+            }
+            SearchMsg::ToggleSelected { uuid, shift } => {
+                let select = !self.selected.contains(&uuid);
+                let range = match (&self.selection_anchor, shift) {
+                    (Some(anchor), true) => {
+                        let order: Vec<&String> =
+                            self.sorted_results().into_iter().map(|r| &r.uuid).collect();
+                        let from = order.iter().position(|u| *u == anchor);
+                        let to = order.iter().position(|u| **u == uuid);
+                        match (from, to) {
+                            (Some(from), Some(to)) => {
+                                let (lo, hi) = (from.min(to), from.max(to));
+                                order[lo..=hi].iter().map(|u| (*u).clone()).collect()
+                            }
+                            _ => vec![uuid.clone()],
+                        }
+                    }
+                    _ => vec![uuid.clone()],
+                };
+                for u in range {
+                    if select {
+                        self.selected.insert(u);
+                    } else {
+                        self.selected.remove(&u);
+                    }
+                }
+                self.selection_anchor = Some(uuid);
+                true
+            }
+            SearchMsg::SelectPage => {
+                let (items, _, _) = self.current_page();
+                let uuids: Vec<String> = items.into_iter().map(|r| r.uuid.clone()).collect();
+                self.selected.extend(uuids);
+                true
+            }
+            SearchMsg::ClearSelection => {
+                self.selected.clear();
+                self.selection_anchor = None;
+                true
+            }
+            SearchMsg::SetRetagInput(s) => {
+                self.retag_input = s;
+                true
+            }
+            SearchMsg::RunBatch(action) => {
+                if self.running_batch.is_some() {
+                    return false;
+                }
+                let items: Vec<SearchResult> = self
+                    .sorted_results()
+                    .into_iter()
+                    .filter(|r| self.selected.contains(&r.uuid))
+                    .cloned()
+                    .collect();
+                if items.is_empty() {
+                    return false;
+                }
+                if let Some(question) = action.confirmation(items.len()) {
+                    if !gloo::dialogs::confirm(&question) {
+                        return false;
+                    }
+                }
+                self.running_batch = Some(action.clone());
+                self.run_batch(ctx, action, items);
+                true
+            }
+            SearchMsg::BatchFinished(action, results) => {
+                self.running_batch = None;
+                let mut succeeded = 0;
+                for (uuid, result) in results {
+                    match result {
+                        Ok(()) => {
+                            succeeded += 1;
+                            self.apply_batch_result(&action, &uuid);
+                        }
+                        Err(why) => self.alerts.push(html!(
+                            <Alert style="danger" text={why} />
+                        )),
+                    }
+                }
+                if succeeded > 0 {
+                    self.alerts.push(html!(
+                        <Alert style="success" text={format!("{} {succeeded} images: done.", action.describe())} />
+                    ));
+                }
+                if action == BatchAction::Reclassify && succeeded > 0 {
+                    if let Some(navigator) = ctx.link().navigator() {
+                        navigator.push(&Route::Home);
+                    }
+                }
+                true
+            }
+            SearchMsg::ReanalysisFinished(entries) => {
+                self.running_batch = None;
+                self.reanalysis = Some(Rc::new(entries));
+                true
+            }
+            SearchMsg::UpdateReanalyzedTags(uuids) => {
+                let Some(entries) = &self.reanalysis else {
+                    return false;
+                };
+                let updates: Vec<(SearchResult, Vec<String>)> = entries
+                    .iter()
+                    .filter(|e| uuids.contains(&e.item.uuid))
+                    .filter_map(|e| Some((e.item.clone(), e.retagged()?)))
+                    .collect();
                 ctx.link().send_future(async move {
-                    let client = reqwest::Client::new();
-
-                    let query = urlencoding::encode(&query);
-                    let request = client
-                        .get(&format!("{SEARCH_URL}?tags={}", query))
-                        .send()
-                        .await;
-
-                    let result = match request {
-                        Ok(resp) => match resp.error_for_status() {
-                            Ok(resp) => match resp.json().await {
-                                Ok(d) => Ok(d),
-                                Err(why) => Err(format!("Error in search JSON: {why}")),
-                            },
-                            Err(why) => Err(format!("Error status in search: {why}")),
-                        },
-                        Err(why) => Err(format!("Error sending search request: {why}")),
-                    };
-
-                    SearchMsg::RecvResults(result)
+                    let mut results = vec![];
+                    for (item, tags) in updates {
+                        let result = api::update_tags(&item, &tags).await;
+                        results.push((item.uuid, tags, result));
+                    }
+                    SearchMsg::ReanalyzedTagsUpdated(results)
                 });
+                false
+            }
+            SearchMsg::ReanalyzedTagsUpdated(results) => {
+                for (uuid, tags, result) in results {
+                    if let Err(why) = result {
+                        self.alerts.push(html!(<Alert style="danger" text={why} />));
+                        continue;
+                    }
+                    if let SearchState::Results(items) = &mut self.state {
+                        if let Some(item) = items.iter_mut().find(|r| r.uuid == uuid) {
+                            item.tags = tags.clone();
+                        }
+                    }
+                    if let Some(entries) = &mut self.reanalysis {
+                        if let Some(entry) = Rc::make_mut(entries)
+                            .iter_mut()
+                            .find(|e| e.item.uuid == uuid)
+                        {
+                            entry.item.tags = tags;
+                        }
+                    }
+                }
+                true
+            }
+            SearchMsg::CloseReanalysis => {
+                self.reanalysis = None;
                 true
             }
             SearchMsg::RecvResults(res) => {
+                self.selected.clear();
+                self.selection_anchor = None;
                 match res {
                     Ok(ent) => self.state = SearchState::Results(ent),
                     Err(why) => self.state = SearchState::Error(why),
@@ -147,3 +599,379 @@ impl Component for Search {
         }
     }
 }
+
+impl Search {
+    /// Record the current search parameters as a new history entry,
+    /// unless the URL already holds them, so that Back is not a no-op.
+    fn push_params(&self, ctx: &Context<Self>) {
+        let current = ctx
+            .link()
+            .location()
+            .map(|l| SearchParams::from_location(&l));
+        if current.as_ref() == Some(&self.params) {
+            return;
+        }
+        if let Some(navigator) = ctx.link().navigator() {
+            if let Err(why) = navigator.push_with_query(&Route::Search, &self.params) {
+                log::warn!("Failed to store search parameters in the URL: {why}");
+            }
+        }
+    }
+
+    fn fetch_results(&mut self, ctx: &Context<Self>) {
+        self.state = SearchState::Running;
+        let query = self.params.q.clone();
+        ctx.link()
+            .send_future(async move { SearchMsg::RecvResults(api::search(&query).await) });
+    }
+
+    fn fetch_tags(ctx: &Context<Self>) {
+        ctx.link()
+            .send_future(async move { SearchMsg::RecvTags(api::tags().await) });
+    }
+
+    fn sorted_results(&self) -> Vec<&SearchResult> {
+        let SearchState::Results(items) = &self.state else {
+            return vec![];
+        };
+        let mut items: Vec<&SearchResult> = items.iter().collect();
+        match self.params.sort {
+            SortOrder::Relevance => items.sort_by(|a, b| {
+                // Server order is kept for tag searches, where there are no scores.
+                b.score
+                    .partial_cmp(&a.score)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            }),
+            SortOrder::NameAsc => items.sort_by(|a, b| a.filename.cmp(&b.filename)),
+            SortOrder::NameDesc => items.sort_by(|a, b| b.filename.cmp(&a.filename)),
+        }
+        items
+    }
+
+    /// The results on the current page, the current page number and the page count.
+    fn current_page(&self) -> (Vec<&SearchResult>, usize, usize) {
+        let items = self.sorted_results();
+        let page_count = items.len().div_ceil(RESULTS_PER_PAGE);
+        let page = self.params.page.clamp(1, page_count.max(1));
+        let items = items
+            .into_iter()
+            .skip((page - 1) * RESULTS_PER_PAGE)
+            .take(RESULTS_PER_PAGE)
+            .collect();
+        (items, page, page_count)
+    }
+
+    fn run_batch(&self, ctx: &Context<Self>, action: BatchAction, items: Vec<SearchResult>) {
+        let queue = ctx
+            .link()
+            .context::<AnalysisQueue>(Callback::noop())
+            .map(|(queue, _)| queue);
+        ctx.link().send_future(async move {
+            let mut results = vec![];
+            match &action {
+                BatchAction::AddTags(_) | BatchAction::ReplaceTags(_) => {
+                    for item in items {
+                        let tags = action.apply_tags(&item.tags).unwrap_or_default();
+                        results.push((item.uuid.clone(), api::update_tags(&item, &tags).await));
+                    }
+                }
+                BatchAction::Delete => {
+                    for item in items {
+                        results.push((item.uuid.clone(), api::delete(&item).await));
+                    }
+                }
+                BatchAction::DownloadZip => {
+                    let mut zip = ZipWriter::new();
+                    let mut manifest = String::new();
+                    crate::csv::write_record(
+                        &mut manifest,
+                        &["path", "uuid", "filename", "tags", "download"],
+                        ',',
+                    );
+                    for item in items {
+                        match api::download(&item).await {
+                            Ok(file) => {
+                                let path = zip.add_blob(
+                                    &format!("images/{}", sanitize_path_component(&file.name)),
+                                    &file.blob,
+                                    file.crc32,
+                                );
+                                crate::csv::write_record(
+                                    &mut manifest,
+                                    &[
+                                        path.as_str(),
+                                        &item.uuid,
+                                        &item.filename,
+                                        &item.tags.join(" "),
+                                        &item.download,
+                                    ],
+                                    ',',
+                                );
+                                results.push((item.uuid.clone(), Ok(())));
+                            }
+                            Err(why) => results.push((item.uuid.clone(), Err(why))),
+                        }
+                    }
+                    zip.add_file("manifest.csv", manifest.as_bytes());
+                    download_blob(zip.finish(), "search_results.zip");
+                }
+                BatchAction::Reanalyze => {
+                    let mut entries = vec![];
+                    for item in items {
+                        let new = api::reanalyze(&item).await;
+                        entries.push(ReanalysisEntry {
+                            old: item.stored_analysis(),
+                            item,
+                            new,
+                        });
+                    }
+                    return SearchMsg::ReanalysisFinished(entries);
+                }
+                BatchAction::Reclassify => {
+                    for item in items {
+                        let result = match (api::download(&item).await, &queue) {
+                            (Ok(file), Some(queue)) => {
+                                queue.push(file);
+                                Ok(())
+                            }
+                            (Ok(_), None) => Err("The analysis view is not available".to_string()),
+                            (Err(why), _) => Err(why),
+                        };
+                        results.push((item.uuid.clone(), result));
+                    }
+                }
+            }
+            SearchMsg::BatchFinished(action, results)
+        });
+    }
+
+    /// Reflect a successful batch operation on one item in the local results.
+    fn apply_batch_result(&mut self, action: &BatchAction, uuid: &str) {
+        let SearchState::Results(items) = &mut self.state else {
+            return;
+        };
+        match action {
+            BatchAction::Delete => {
+                items.retain(|r| r.uuid != uuid);
+                self.selected.remove(uuid);
+            }
+            BatchAction::AddTags(_) | BatchAction::ReplaceTags(_) => {
+                if let Some(item) = items.iter_mut().find(|r| r.uuid == uuid) {
+                    if let Some(tags) = action.apply_tags(&item.tags) {
+                        item.tags = tags;
+                    }
+                }
+            }
+            BatchAction::DownloadZip | BatchAction::Reclassify | BatchAction::Reanalyze => {}
+        }
+    }
+
+    fn view_batch_toolbar(&self, ctx: &Context<Self>) -> Html {
+        if let Some(action) = &self.running_batch {
+            return html! {
+                <p>{format!("{} {} images...", action.describe(), self.selected.len())}<div class="spinner-border" role="status"></div></p>
+            };
+        }
+
+        let new_tags: Vec<String> = self
+            .retag_input
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        let add_tags = BatchAction::AddTags(new_tags.clone());
+        let replace_tags = BatchAction::ReplaceTags(new_tags.clone());
+        let batch = |action: BatchAction| {
+            ctx.link()
+                .callback(move |_| SearchMsg::RunBatch(action.clone()))
+        };
+        let nothing_selected = self.selected.is_empty();
+
+        html! {
+            <div class="card mb-3">
+                <div class="card-body">
+                    <div class="mb-2">
+                        <span class="me-2">{format!("{} selected", self.selected.len())}</span>
+                        <button class="btn btn-sm btn-outline-secondary me-2" onclick={ctx.link().callback(|_| SearchMsg::SelectPage)}>{"Select all on page"}</button>
+                        <button class="btn btn-sm btn-outline-secondary" disabled={nothing_selected} onclick={ctx.link().callback(|_| SearchMsg::ClearSelection)}>{"Clear selection"}</button>
+                    </div>
+                    <div class="row g-2">
+                        <div class="col-6">
+                            <div class="input-group">
+                                <input type="text" class="form-control" placeholder="new tags ..." value={self.retag_input.clone()}
+                                    oninput={ctx.link().callback(|ev: InputEvent| SearchMsg::SetRetagInput(get_text(ev)))} />
+                                <button class="btn btn-outline-primary" disabled={nothing_selected || new_tags.is_empty()} onclick={batch(add_tags)}>{"Add tags"}</button>
+                                <button class="btn btn-outline-warning" disabled={nothing_selected || new_tags.is_empty()} onclick={batch(replace_tags)}>{"Replace tags"}</button>
+                            </div>
+                        </div>
+                        <div class="col-6">
+                            <button class="btn btn-primary me-2" disabled={nothing_selected} onclick={batch(BatchAction::DownloadZip)}>{"Download as ZIP"}</button>
+                            <button class="btn btn-success me-2" disabled={nothing_selected} onclick={batch(BatchAction::Reclassify)}>{"Re-classify"}</button>
+                            <button class="btn btn-info me-2" disabled={nothing_selected} onclick={batch(BatchAction::Reanalyze)}>{"Re-analyze and compare"}</button>
+                            <button class="btn btn-danger" disabled={nothing_selected} onclick={batch(BatchAction::Delete)}>{"Delete"}</button>
+                        </div>
+                    </div>
+                </div>
+            </div>
+        }
+    }
+
+    /// Tags starting with the word currently being typed, most used first.
+    fn suggestions(&self) -> Vec<(&String, u64)> {
+        let Ok(tags) = &self.tags else {
+            return vec![];
+        };
+        if self.query.ends_with(char::is_whitespace) {
+            return vec![];
+        }
+        let Some(partial) = self.query.split_whitespace().last() else {
+            return vec![];
+        };
+        let partial = partial.to_lowercase();
+        let mut matches: Vec<(&String, u64)> = tags
+            .iter()
+            .filter(|(tag, _)| tag.to_lowercase().starts_with(&partial) && **tag != partial)
+            .map(|(tag, count)| (tag, *count))
+            .collect();
+        matches.sort_by(|(t1, c1), (t2, c2)| c2.cmp(c1).then(t1.cmp(t2)));
+        matches.truncate(MAX_SUGGESTIONS);
+        matches
+    }
+
+    fn view_suggestions(&self, ctx: &Context<Self>) -> Html {
+        let suggestions = self.suggestions();
+        let unknown: Vec<&str> = match &self.tags {
+            Ok(tags) if !tags.is_empty() => self
+                .params
+                .q
+                .split_whitespace()
+                .filter(|w| !tags.contains_key(*w))
+                .collect(),
+            _ => vec![],
+        };
+
+        if !suggestions.is_empty() {
+            html! {
+                <div class="list-group mb-3">
+                    { for suggestions.into_iter().map(|(tag, count)| {
+                        let onclick = {
+                            let tag = tag.clone();
+                            ctx.link().callback(move |_| SearchMsg::CompleteTag(tag.clone()))
+                        };
+                        html! {
+                            <button type="button" class="list-group-item list-group-item-action d-flex justify-content-between" {onclick}>
+                                {tag}<span class="badge text-bg-secondary">{count}</span>
+                            </button>
+                        }
+                    }) }
+                </div>
+            }
+        } else if !unknown.is_empty() {
+            html! {
+                <p class="text-warning">{format!("No archived images are tagged with: {}", unknown.join(", "))}</p>
+            }
+        } else {
+            html!()
+        }
+    }
+
+    fn mode_tab(&self, ctx: &Context<Self>, mode: SearchMode, label: &str) -> Html {
+        let onclick = ctx.link().callback(move |ev: MouseEvent| {
+            ev.prevent_default();
+            SearchMsg::SetMode(mode)
+        });
+        html! {
+            <li class="nav-item">
+                <a class={classes!("nav-link", (self.params.mode == mode).then_some("active"))} href="#" {onclick}>{label}</a>
+            </li>
+        }
+    }
+
+    fn view_query_input(&self, ctx: &Context<Self>) -> Html {
+        match self.params.mode {
+            SearchMode::Tags => html! {
+                <>
+                    <div class="input-group mb-3">
+                        <input type="text" class="form-control" placeholder="tag1 tag2 tag3 ..." value={self.query.clone()}
+                            oninput={ctx.link().callback(|ev: InputEvent| SearchMsg::SetSearchQuery(get_text(ev)))}
+                            onkeydown={ctx.link().batch_callback(|ev: KeyboardEvent| (ev.key() == "Enter").then_some(SearchMsg::RunSearch))}/>
+                        <button class="btn btn-outline-primary" type="button" onclick={ctx.link().callback(|_| SearchMsg::RunSearch)}>{"Search"}</button>
+                        <button class={classes!("btn", "btn-outline-secondary", self.show_tag_cloud.then_some("active"))} type="button" onclick={ctx.link().callback(|_| SearchMsg::ToggleTagCloud)}>{"Browse tags"}</button>
+                    </div>
+                    {self.view_suggestions(ctx)}
+                </>
+            },
+            SearchMode::Image => {
+                let example = match &self.example {
+                    Some(example) => {
+                        html!(<ImageDisplayBox image_data={example.clone()} class={classes!("col-3")} />)
+                    }
+                    None => html!(),
+                };
+                html! {
+                    <div class="row mb-3">
+                        <div class="col">
+                            <FileUploadBox on_image={ctx.link().callback(|file| SearchMsg::SearchByExample(Box::new(file)))} />
+                        </div>
+                        {example}
+                    </div>
+                }
+            }
+        }
+    }
+
+    fn view_reanalysis(&self, ctx: &Context<Self>) -> Html {
+        match &self.reanalysis {
+            Some(entries) => html! {
+                <ReanalysisDiff entries={entries.clone()}
+                    on_update_tags={ctx.link().callback(SearchMsg::UpdateReanalyzedTags)}
+                    on_close={ctx.link().callback(|_| SearchMsg::CloseReanalysis)} />
+            },
+            None => html!(),
+        }
+    }
+
+    fn view_tag_cloud(&self, ctx: &Context<Self>) -> Html {
+        if !self.show_tag_cloud || self.params.mode != SearchMode::Tags {
+            return html!();
+        }
+        let contents = match &self.tags {
+            Ok(tags) => {
+                let selected: Vec<String> =
+                    self.query.split_whitespace().map(String::from).collect();
+                html!(<TagCloud tags={tags.clone()} {selected} on_click={ctx.link().callback(SearchMsg::AddTag)} />)
+            }
+            Err(why) => html!(<Alert style="danger" text={why.clone()} />),
+        };
+        html! {
+            <div class="card mb-3">
+                <div class="card-body">{contents}</div>
+            </div>
+        }
+    }
+
+    fn view_pagination(&self, ctx: &Context<Self>, page: usize, page_count: usize) -> Html {
+        if page_count <= 1 {
+            return html!();
+        }
+        let page_link = |target: usize, label: String, disabled: bool, active: bool| {
+            let onclick = ctx.link().callback(move |ev: MouseEvent| {
+                ev.prevent_default();
+                SearchMsg::SetPage(target)
+            });
+            html! {
+                <li class={classes!("page-item", disabled.then_some("disabled"), active.then_some("active"))}>
+                    <a class="page-link" href="#" {onclick}>{label}</a>
+                </li>
+            }
+        };
+        html! {
+            <nav>
+                <ul class="pagination">
+                    {page_link(page.saturating_sub(1), "Previous".to_string(), page == 1, false)}
+                    { for (1..=page_count).map(|p| page_link(p, p.to_string(), false, p == page)) }
+                    {page_link(page + 1, "Next".to_string(), page == page_count, false)}
+                </ul>
+            </nav>
+        }
+    }
+}
@@ -0,0 +1,365 @@
+//! A minimal ZIP writer that stores entries without compression.
+//!
+//! Images are already compressed, so deflating them again only costs time.
+//! The archive is assembled by the browser as a `Blob` made of parts: the headers are
+//! small byte arrays, and file contents that are already blobs are referenced rather
+//! than copied, so neither WASM nor JS memory ever holds the archive as a whole.
+//!
+//! Archives over 4 GiB or with more than 65535 entries use the ZIP64 extensions.
+
+use std::collections::HashSet;
+
+use gloo::file::Blob;
+use js_sys::{Array, Uint8Array};
+
+/// General purpose flag: file names are UTF-8 (our class names are not ASCII).
+const FLAG_UTF8: u16 = 1 << 11;
+/// Version needed to extract a plain stored entry.
+const VERSION_DEFAULT: u16 = 20;
+/// Version needed to extract an entry or archive with ZIP64 fields.
+const VERSION_ZIP64: u16 = 45;
+/// ID of the extra field that holds ZIP64 sizes and offsets.
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+/// Stored in a 32-bit field whose real value is in the ZIP64 extra field.
+const SATURATED_U32: u32 = u32::MAX;
+/// Stored in a 16-bit entry count whose real value is in the ZIP64 end record.
+const SATURATED_U16: u16 = u16::MAX;
+
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    path: String,
+    crc32: u32,
+    size: u64,
+    /// Where the entry's local header starts.
+    offset: u64,
+}
+
+impl Entry {
+    fn needs_zip64(&self) -> bool {
+        self.size >= SATURATED_U32 as u64 || self.offset >= SATURATED_U32 as u64
+    }
+}
+
+/// Lower 32 bits of a size or offset, or the marker that says it is in the ZIP64 fields.
+fn u32_or_saturated(value: u64) -> u32 {
+    u32::try_from(value)
+        .ok()
+        .filter(|v| *v != SATURATED_U32)
+        .unwrap_or(SATURATED_U32)
+}
+
+fn local_header(entry: &Entry, dos_date: u16, dos_time: u16) -> Vec<u8> {
+    let zip64 = entry.size >= SATURATED_U32 as u64;
+    let mut header = Vec::with_capacity(30 + entry.path.len() + 20);
+    header.extend(0x04034b50u32.to_le_bytes());
+    let version = if zip64 {
+        VERSION_ZIP64
+    } else {
+        VERSION_DEFAULT
+    };
+    header.extend(version.to_le_bytes()); // version needed to extract
+    header.extend(FLAG_UTF8.to_le_bytes());
+    header.extend(0u16.to_le_bytes()); // stored
+    header.extend(dos_time.to_le_bytes());
+    header.extend(dos_date.to_le_bytes());
+    header.extend(entry.crc32.to_le_bytes());
+    header.extend(u32_or_saturated(entry.size).to_le_bytes()); // compressed size
+    header.extend(u32_or_saturated(entry.size).to_le_bytes()); // uncompressed size
+    header.extend((entry.path.len() as u16).to_le_bytes());
+    header.extend((if zip64 { 20u16 } else { 0 }).to_le_bytes()); // extra field length
+    header.extend(entry.path.as_bytes());
+    if zip64 {
+        header.extend(ZIP64_EXTRA_ID.to_le_bytes());
+        header.extend(16u16.to_le_bytes());
+        header.extend(entry.size.to_le_bytes()); // uncompressed
+        header.extend(entry.size.to_le_bytes()); // compressed
+    }
+    header
+}
+
+fn central_directory(entries: &[Entry], dos_date: u16, dos_time: u16) -> Vec<u8> {
+    let mut directory = vec![];
+    for entry in entries.iter() {
+        // Only the fields whose 32-bit slots are saturated go into the extra field, in this order.
+        let mut extra = vec![];
+        if entry.size >= SATURATED_U32 as u64 {
+            extra.extend(entry.size.to_le_bytes()); // uncompressed
+            extra.extend(entry.size.to_le_bytes()); // compressed
+        }
+        if entry.offset >= SATURATED_U32 as u64 {
+            extra.extend(entry.offset.to_le_bytes());
+        }
+        let version = if entry.needs_zip64() {
+            VERSION_ZIP64
+        } else {
+            VERSION_DEFAULT
+        };
+
+        directory.extend(0x02014b50u32.to_le_bytes());
+        directory.extend(version.to_le_bytes()); // version made by
+        directory.extend(version.to_le_bytes()); // version needed to extract
+        directory.extend(FLAG_UTF8.to_le_bytes());
+        directory.extend(0u16.to_le_bytes()); // stored
+        directory.extend(dos_time.to_le_bytes());
+        directory.extend(dos_date.to_le_bytes());
+        directory.extend(entry.crc32.to_le_bytes());
+        directory.extend(u32_or_saturated(entry.size).to_le_bytes());
+        directory.extend(u32_or_saturated(entry.size).to_le_bytes());
+        directory.extend((entry.path.len() as u16).to_le_bytes());
+        let extra_len = if extra.is_empty() { 0 } else { extra.len() + 4 };
+        directory.extend((extra_len as u16).to_le_bytes());
+        directory.extend(0u16.to_le_bytes()); // comment length
+        directory.extend(0u16.to_le_bytes()); // disk number
+        directory.extend(0u16.to_le_bytes()); // internal attributes
+        directory.extend(0u32.to_le_bytes()); // external attributes
+        directory.extend(u32_or_saturated(entry.offset).to_le_bytes());
+        directory.extend(entry.path.as_bytes());
+        if !extra.is_empty() {
+            directory.extend(ZIP64_EXTRA_ID.to_le_bytes());
+            directory.extend((extra.len() as u16).to_le_bytes());
+            directory.extend(extra);
+        }
+    }
+    directory
+}
+
+/// The end of central directory record, preceded by the ZIP64 end record and locator
+/// when the entry count, directory size or directory offset does not fit the classic one.
+fn end_records(entry_count: usize, directory_size: u64, directory_offset: u64) -> Vec<u8> {
+    let count = entry_count as u64;
+    let zip64 = count >= SATURATED_U16 as u64
+        || directory_size >= SATURATED_U32 as u64
+        || directory_offset >= SATURATED_U32 as u64;
+
+    let mut end = vec![];
+    if zip64 {
+        let zip64_end_offset = directory_offset + directory_size;
+        end.extend(0x06064b50u32.to_le_bytes());
+        end.extend(44u64.to_le_bytes()); // size of the rest of this record
+        end.extend(VERSION_ZIP64.to_le_bytes()); // version made by
+        end.extend(VERSION_ZIP64.to_le_bytes()); // version needed to extract
+        end.extend(0u32.to_le_bytes()); // this disk
+        end.extend(0u32.to_le_bytes()); // disk with central directory
+        end.extend(count.to_le_bytes());
+        end.extend(count.to_le_bytes());
+        end.extend(directory_size.to_le_bytes());
+        end.extend(directory_offset.to_le_bytes());
+
+        end.extend(0x07064b50u32.to_le_bytes());
+        end.extend(0u32.to_le_bytes()); // disk with the ZIP64 end record
+        end.extend(zip64_end_offset.to_le_bytes());
+        end.extend(1u32.to_le_bytes()); // total number of disks
+    }
+
+    let count16 = u16::try_from(count)
+        .ok()
+        .filter(|c| *c != SATURATED_U16)
+        .unwrap_or(SATURATED_U16);
+    end.extend(0x06054b50u32.to_le_bytes());
+    end.extend(0u16.to_le_bytes()); // this disk
+    end.extend(0u16.to_le_bytes()); // disk with central directory
+    end.extend(count16.to_le_bytes());
+    end.extend(count16.to_le_bytes());
+    end.extend(u32_or_saturated(directory_size).to_le_bytes());
+    end.extend(u32_or_saturated(directory_offset).to_le_bytes());
+    end.extend(0u16.to_le_bytes()); // comment length
+    end
+}
+
+pub struct ZipWriter {
+    parts: Array,
+    entries: Vec<Entry>,
+    used_paths: HashSet<String>,
+    offset: u64,
+    dos_time: u16,
+    dos_date: u16,
+}
+
+impl Default for ZipWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ZipWriter {
+    pub fn new() -> Self {
+        let (dos_date, dos_time) = dos_timestamp(&js_sys::Date::new_0());
+        Self {
+            parts: Array::new(),
+            entries: vec![],
+            used_paths: HashSet::new(),
+            offset: 0,
+            dos_time,
+            dos_date,
+        }
+    }
+
+    /// Add a small in-memory file, such as a manifest, returning the path it was stored under.
+    ///
+    /// If the path is already taken, a number is appended to the file name
+    /// (`swan.jpg` becomes `swan (2).jpg`).
+    pub fn add_file(&mut self, path: &str, data: &[u8]) -> String {
+        let path = self.add_header(path, crc32fast::hash(data), data.len() as u64);
+        // `Uint8Array::from` copies, so the Rust buffer can be freed right away.
+        self.parts.push(&Uint8Array::from(data));
+        path
+    }
+
+    /// Add a file that is already a blob, without copying its contents.
+    /// `crc32` must be the checksum of the blob's bytes.
+    pub fn add_blob(&mut self, path: &str, blob: &Blob, crc32: u32) -> String {
+        let path = self.add_header(path, crc32, blob.size());
+        let blob: &web_sys::Blob = blob.as_ref();
+        self.parts.push(blob);
+        path
+    }
+
+    fn add_header(&mut self, path: &str, crc32: u32, size: u64) -> String {
+        let entry = Entry {
+            path: self.unique_path(path),
+            crc32,
+            size,
+            offset: self.offset,
+        };
+        let header = local_header(&entry, self.dos_date, self.dos_time);
+        self.parts.push(&Uint8Array::from(header.as_slice()));
+        self.offset += header.len() as u64 + size;
+        let path = entry.path.clone();
+        self.entries.push(entry);
+        path
+    }
+
+    /// Write the central directory and return the finished archive.
+    pub fn finish(self) -> Blob {
+        let directory = central_directory(&self.entries, self.dos_date, self.dos_time);
+        let end = end_records(self.entries.len(), directory.len() as u64, self.offset);
+        self.parts.push(&Uint8Array::from(directory.as_slice()));
+        self.parts.push(&Uint8Array::from(end.as_slice()));
+
+        let mut options = web_sys::BlobPropertyBag::new();
+        options.type_("application/zip");
+        let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&self.parts, &options)
+            .expect("failed to create ZIP blob");
+        Blob::from(blob)
+    }
+
+    fn unique_path(&mut self, path: &str) -> String {
+        let mut candidate = path.to_string();
+        let mut n = 1;
+        while self.used_paths.contains(&candidate) {
+            n += 1;
+            candidate = match path.rsplit_once('.') {
+                Some((stem, ext)) if !stem.is_empty() && !ext.contains('/') => {
+                    format!("{stem} ({n}).{ext}")
+                }
+                _ => format!("{path} ({n})"),
+            };
+        }
+        self.used_paths.insert(candidate.clone());
+        candidate
+    }
+}
+
+/// Replace characters that are not allowed in file and folder names on common systems.
+pub fn sanitize_path_component(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let cleaned = cleaned.trim().trim_matches('.');
+    if cleaned.is_empty() {
+        "_".to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+fn dos_timestamp(date: &js_sys::Date) -> (u16, u16) {
+    let year = (date.get_full_year() as u16).clamp(1980, 2107);
+    let dos_date =
+        ((year - 1980) << 9) | ((date.get_month() as u16 + 1) << 5) | date.get_date() as u16;
+    let dos_time = ((date.get_hours() as u16) << 11)
+        | ((date.get_minutes() as u16) << 5)
+        | (date.get_seconds() as u16 / 2);
+    (dos_date, dos_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(size: u64, offset: u64) -> Entry {
+        Entry {
+            path: "a.jpg".to_string(),
+            crc32: 0x12345678,
+            size,
+            offset,
+        }
+    }
+
+    fn u16_at(data: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes(data[at..at + 2].try_into().unwrap())
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    fn u64_at(data: &[u8], at: usize) -> u64 {
+        u64::from_le_bytes(data[at..at + 8].try_into().unwrap())
+    }
+
+    #[test]
+    fn small_entries_use_classic_headers() {
+        let header = local_header(&entry(1000, 0), 0, 0);
+        assert_eq!(header.len(), 30 + 5);
+        assert_eq!(u16_at(&header, 4), VERSION_DEFAULT);
+        assert_eq!(u32_at(&header, 18), 1000);
+        assert_eq!(u16_at(&header, 28), 0);
+
+        let end = end_records(3, 100, 5000);
+        assert_eq!(end.len(), 22);
+        assert_eq!(u16_at(&end, 10), 3);
+        assert_eq!(u32_at(&end, 16), 5000);
+    }
+
+    #[test]
+    fn large_entry_sizes_move_to_zip64_extra_field() {
+        let size = 5 << 30;
+        let header = local_header(&entry(size, 0), 0, 0);
+        assert_eq!(u16_at(&header, 4), VERSION_ZIP64);
+        assert_eq!(u32_at(&header, 18), SATURATED_U32);
+        assert_eq!(u16_at(&header, 28), 20);
+        assert_eq!(u16_at(&header, 35), ZIP64_EXTRA_ID);
+        assert_eq!(u64_at(&header, 39), size);
+    }
+
+    #[test]
+    fn large_offsets_move_to_zip64_extra_field() {
+        let offset = 6 << 30;
+        let directory = central_directory(&[entry(10, offset)], 0, 0);
+        assert_eq!(u32_at(&directory, 20), 10);
+        assert_eq!(u32_at(&directory, 42), SATURATED_U32);
+        assert_eq!(u16_at(&directory, 30), 12);
+        assert_eq!(u16_at(&directory, 46 + 5), ZIP64_EXTRA_ID);
+        assert_eq!(u64_at(&directory, 46 + 5 + 4), offset);
+    }
+
+    #[test]
+    fn many_entries_get_zip64_end_records() {
+        let offset = 7 << 30;
+        let end = end_records(70_000, 1234, offset);
+        assert_eq!(end.len(), 56 + 20 + 22);
+        assert_eq!(u32_at(&end, 0), 0x06064b50);
+        assert_eq!(u64_at(&end, 24), 70_000);
+        assert_eq!(u64_at(&end, 48), offset);
+        assert_eq!(u32_at(&end, 56), 0x07064b50);
+        assert_eq!(u64_at(&end, 64), offset + 1234);
+        assert_eq!(u16_at(&end, 76 + 8), SATURATED_U16);
+        assert_eq!(u32_at(&end, 76 + 16), SATURATED_U32);
+    }
+}